@@ -1,4 +1,11 @@
 fn main() {
+    // Sidecar binaries are named `<tool>-<target triple>`; expose the triple
+    // so the tool locator can build those names at runtime.
+    println!(
+        "cargo:rustc-env=TARGET_TRIPLE={}",
+        std::env::var("TARGET").unwrap()
+    );
+
     // Check if exiftool is available in the system
     let exiftool_check = std::process::Command::new("exiftool").arg("-ver").output();
 
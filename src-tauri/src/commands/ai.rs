@@ -0,0 +1,68 @@
+// Tauri command handlers for AI metadata generation
+use crate::services::ai::key_pool::{KeyPoolStatus, KEY_POOL};
+use crate::services::ai::ollama::{self, OllamaModel};
+use crate::services::ai::rate_limit::{RateLimitStatus, RATE_LIMITER};
+use crate::services::ai::usage::{self, PriceTable, UsageRange, UsageReport};
+use crate::services::ai::{self, GenerateOptions, MetadataResult};
+use crate::services::gpu_thumbnail::HardwareState;
+use tauri::{AppHandle, Manager};
+
+/// Generate title, description and keywords for one file with the
+/// configured AI provider
+#[tauri::command]
+pub async fn generate_metadata(
+    app: AppHandle,
+    file_path: String,
+    options: GenerateOptions,
+) -> Result<MetadataResult, String> {
+    let backend = app.state::<HardwareState>().backend();
+    tokio::task::spawn_blocking(move || {
+        ai::generate_metadata(&file_path, &options, backend, None, None)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+    .map_err(|e| e.to_string())
+}
+
+/// Current pacing per provider and API key: request rate, concurrency,
+/// cooldowns after 429/503 responses and recent errors
+#[tauri::command]
+pub fn get_rate_limit_status() -> Vec<RateLimitStatus> {
+    RATE_LIMITER.status()
+}
+
+/// Health and usage of every API key the backend has used this session
+#[tauri::command]
+pub fn get_key_pool_status() -> Vec<KeyPoolStatus> {
+    KEY_POOL.status()
+}
+
+/// Requests, images, tokens and cost over `range`, totalled per day and per
+/// model
+#[tauri::command]
+pub async fn get_usage_report(range: Option<UsageRange>) -> Result<UsageReport, String> {
+    tokio::task::spawn_blocking(move || usage::usage_report(range.unwrap_or_default()))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Per-model prices used to cost requests the provider doesn't price itself
+#[tauri::command]
+pub fn get_price_table() -> PriceTable {
+    usage::load_price_table()
+}
+
+#[tauri::command]
+pub fn set_price_table(table: PriceTable) -> Result<(), String> {
+    usage::save_price_table(&table)
+}
+
+/// Models installed on an Ollama server; `base_url` defaults to
+/// `http://localhost:11434`
+#[tauri::command]
+pub async fn list_ollama_models(base_url: Option<String>) -> Result<Vec<OllamaModel>, String> {
+    tokio::task::spawn_blocking(move || ollama::installed_models(base_url.as_deref()))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+        .map_err(|e| e.to_string())
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::services::ai::cache::get_ai_cache_dir;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CacheDirectory {
     pub name: String,
@@ -70,15 +72,15 @@ fn get_app_cache_path() -> Option<PathBuf> {
 pub fn get_cache_info() -> Result<Vec<CacheDirectory>, String> {
     let mut caches: Vec<CacheDirectory> = Vec::new();
 
-    if let Some(cache_path) = get_app_cache_path() {
-        let os_type = if cfg!(target_os = "windows") {
-            "windows"
-        } else if cfg!(target_os = "macos") {
-            "macos"
-        } else {
-            "linux"
-        };
+    let os_type = if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else {
+        "linux"
+    };
 
+    if let Some(cache_path) = get_app_cache_path() {
         let size = if cache_path.exists() {
             get_dir_size(&cache_path)
         } else {
@@ -93,6 +95,21 @@ pub fn get_cache_info() -> Result<Vec<CacheDirectory>, String> {
         });
     }
 
+    if let Some(ai_path) = get_ai_cache_dir() {
+        let size = if ai_path.exists() {
+            get_dir_size(&ai_path)
+        } else {
+            0
+        };
+
+        caches.push(CacheDirectory {
+            name: "Descify AI Responses".to_string(),
+            path: ai_path.to_string_lossy().to_string(),
+            size_bytes: size,
+            os_type: os_type.to_string(),
+        });
+    }
+
     Ok(caches)
 }
 
@@ -0,0 +1,21 @@
+// Tauri command handlers for hardware acceleration status
+use crate::services::gpu_thumbnail::{HardwareState, HardwareStatus};
+use tauri::State;
+
+/// Report the GPU/ffmpeg capabilities detected at startup and the backend
+/// video jobs are currently using
+#[tauri::command]
+pub fn get_hardware_capabilities(hardware: State<'_, HardwareState>) -> HardwareStatus {
+    hardware.status()
+}
+
+/// Force video frame extraction onto the CPU, or go back to the detected
+/// hardware backend.  The choice is remembered across restarts.
+#[tauri::command]
+pub fn set_force_cpu_video(
+    hardware: State<'_, HardwareState>,
+    force_cpu: bool,
+) -> Result<HardwareStatus, String> {
+    hardware.set_force_cpu(force_cpu)?;
+    Ok(hardware.status())
+}
@@ -0,0 +1,66 @@
+// Tauri command handlers for backend batch jobs
+use crate::models::jobs::{BatchJob, BatchJobSpec, BatchJobSummary};
+use crate::services::gpu_thumbnail::HardwareState;
+use crate::services::jobs::JobManager;
+use tauri::{AppHandle, Manager, State};
+
+// Starting and resuming check the provider's keys, which reads the keyring
+// over D-Bus and deadlocks on the main thread, so both run on the blocking
+// pool.
+
+/// Start processing folders in the background.  Progress is reported on the
+/// `batch-job-progress` event.
+#[tauri::command]
+pub async fn start_batch_job(
+    app: AppHandle,
+    spec: BatchJobSpec,
+) -> Result<BatchJobSummary, String> {
+    let backend = app.state::<HardwareState>().backend();
+    tokio::task::spawn_blocking(move || app.state::<JobManager>().start(spec, backend))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Stop picking up new files; files already in flight finish first
+#[tauri::command]
+pub fn pause_batch_job(
+    jobs: State<'_, JobManager>,
+    job_id: String,
+) -> Result<BatchJobSummary, String> {
+    jobs.pause(&job_id)
+}
+
+/// Continue a paused or interrupted job with the stored provider keys
+#[tauri::command]
+pub async fn resume_batch_job(app: AppHandle, job_id: String) -> Result<BatchJobSummary, String> {
+    let backend = app.state::<HardwareState>().backend();
+    tokio::task::spawn_blocking(move || app.state::<JobManager>().resume(&job_id, backend))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Cancel a job, killing any exiftool process it has running
+#[tauri::command]
+pub fn cancel_batch_job(
+    jobs: State<'_, JobManager>,
+    job_id: String,
+) -> Result<BatchJobSummary, String> {
+    jobs.cancel(&job_id)
+}
+
+/// Full per-file state of a job, for rebuilding the UI after a reload
+#[tauri::command]
+pub fn get_batch_job(jobs: State<'_, JobManager>, job_id: String) -> Result<BatchJob, String> {
+    jobs.get(&job_id)
+}
+
+#[tauri::command]
+pub fn list_batch_jobs(jobs: State<'_, JobManager>) -> Vec<BatchJobSummary> {
+    jobs.list()
+}
+
+/// Remove a finished, paused or cancelled job and its checkpoint
+#[tauri::command]
+pub fn delete_batch_job(jobs: State<'_, JobManager>, job_id: String) -> Result<(), String> {
+    jobs.delete(&job_id)
+}
@@ -4,6 +4,7 @@ use crate::services::exiftool::{
     build_exiftool_command, execute_exiftool, get_exiftool_path, has_metadata, read_exif_metadata,
     validate_file,
 };
+use crate::services::video_probe::{probe_video, VideoInfo};
 
 /// Embed metadata into image/video files using exiftool
 #[tauri::command]
@@ -27,7 +28,7 @@ pub async fn embed_metadata(request: EmbedMetadataRequest) -> Result<EmbedMetada
 
     // Build and execute exiftool command
     let cmd = build_exiftool_command(&exiftool_path, &request);
-    execute_exiftool(cmd, &request, &exiftool_path)
+    execute_exiftool(cmd, &request, &exiftool_path, None)
 }
 
 /// Read EXIF metadata from an image/video file
@@ -35,3 +36,11 @@ pub async fn embed_metadata(request: EmbedMetadataRequest) -> Result<EmbedMetada
 pub async fn read_exif_metadata_command(file_path: String) -> Result<ExifData, String> {
     read_exif_metadata(&file_path)
 }
+
+/// Read stream information (size, rotation, duration, codec...) from a video file
+#[tauri::command]
+pub async fn read_video_info(file_path: String) -> Result<VideoInfo, String> {
+    tokio::task::spawn_blocking(move || probe_video(&file_path))
+        .await
+        .map_err(|e| format!("Video probe task failed: {}", e))?
+}
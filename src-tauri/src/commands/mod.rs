@@ -1,3 +1,9 @@
+pub mod ai;
 pub mod cache;
+pub mod hardware;
+pub mod jobs;
 pub mod metadata;
+pub mod secrets;
+pub mod templates;
 pub mod thumbnail;
+pub mod tools;
@@ -0,0 +1,33 @@
+// Tauri command handlers for provider API key storage.  Keys go in but never
+// come back out; the UI only sees ids and hints.
+use crate::services::ai::ProviderKind;
+use crate::services::secrets::{self, StoredKey};
+
+// Keyring calls block on D-Bus, which deadlocks on the main thread, so every
+// command runs them on the blocking pool.
+
+/// Save an API key for `provider` in the system keyring (or the encrypted
+/// fallback file), adding it to that provider's key pool
+#[tauri::command]
+pub async fn store_api_key(provider: ProviderKind, api_key: String) -> Result<StoredKey, String> {
+    tokio::task::spawn_blocking(move || secrets::store_api_key(provider, &api_key))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// List stored keys by id and hint; the keys themselves are never returned
+#[tauri::command]
+pub async fn list_api_keys() -> Result<Vec<StoredKey>, String> {
+    tokio::task::spawn_blocking(secrets::list_api_keys)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Delete one stored key by id, or all keys for `provider` when `key_id`
+/// is omitted
+#[tauri::command]
+pub async fn delete_api_key(provider: ProviderKind, key_id: Option<String>) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || secrets::delete_api_key(provider, key_id.as_deref()))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
@@ -0,0 +1,36 @@
+// Tauri command handlers for prompt templates
+use crate::models::jobs::MetadataLimits;
+use crate::services::template::{self, TemplateContext, TemplateValidation, TemplateVariable};
+
+/// Check a template for syntax errors, unknown variables and missing limit
+/// placeholders; call before saving it
+#[tauri::command]
+pub fn validate_prompt_template(template: String) -> TemplateValidation {
+    template::validate(&template)
+}
+
+/// Variables available to templates, with descriptions for the editor
+#[tauri::command]
+pub fn get_template_variables() -> Vec<TemplateVariable> {
+    template::template_variables()
+}
+
+/// Render a template for `file_path`, or with just the limits when no file
+/// is given (for previews)
+#[tauri::command]
+pub async fn render_prompt_template(
+    template: String,
+    file_path: Option<String>,
+    limits: Option<MetadataLimits>,
+) -> Result<String, String> {
+    let limits = limits.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        let context = match file_path {
+            Some(path) => TemplateContext::for_file(&path, &limits),
+            None => TemplateContext::with_limits(&limits),
+        };
+        template::render(&template, &context).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
@@ -1,26 +1,34 @@
+use crate::services::gpu_thumbnail::HardwareState;
 use crate::services::thumbnail::{
-    generate_preview, generate_thumbnail, generate_video_preview, generate_video_thumbnail,
-    PreviewResult, ThumbnailResult,
+    extract_video_keyframes, generate_preview, generate_thumbnail, generate_video_preview,
+    generate_video_scrub_strip, generate_video_thumbnail, KeyframeSelection, OutputOptions,
+    PreviewResult, ResizeOptions, ScrubStripResult, ThumbnailResult, VideoKeyframesResult,
 };
 use std::collections::HashMap;
-use tauri::command;
+use tauri::{command, AppHandle, Manager};
 
 const DEFAULT_THUMBNAIL_SIZE: u32 = 720;
 const DEFAULT_PREVIEW_SIZE: u32 = 1920;
+const DEFAULT_KEYFRAME_COUNT: u32 = 6;
+const DEFAULT_KEYFRAME_SIZE: u32 = 512;
+const DEFAULT_SCRUB_FRAME_COUNT: u32 = 10;
+const DEFAULT_SCRUB_FRAME_SIZE: u32 = 240;
 
 #[command]
 pub async fn get_native_thumbnail_command(file_path: String) -> ThumbnailResult {
     let path = file_path.clone();
-    tokio::task::spawn_blocking(move || generate_thumbnail(&path, DEFAULT_THUMBNAIL_SIZE))
-        .await
-        .unwrap_or_else(|_| ThumbnailResult {
-            thumbnail_base64: None,
-            cache_path: None,
-            width: None,
-            height: None,
-            file_size: None,
-            from_cache: false,
-        })
+    tokio::task::spawn_blocking(move || {
+        generate_thumbnail(&path, DEFAULT_THUMBNAIL_SIZE, &OutputOptions::default())
+    })
+    .await
+    .unwrap_or_else(|_| ThumbnailResult {
+        thumbnail_base64: None,
+        cache_path: None,
+        width: None,
+        height: None,
+        file_size: None,
+        from_cache: false,
+    })
 }
 
 #[command]
@@ -28,11 +36,12 @@ pub async fn get_native_thumbnails_batch(
     file_paths: Vec<String>,
 ) -> HashMap<String, ThumbnailResult> {
     let paths = file_paths.clone();
+    let output = OutputOptions::default();
     tokio::task::spawn_blocking(move || {
         paths
             .into_iter()
             .map(|file_path| {
-                let result = generate_thumbnail(&file_path, DEFAULT_THUMBNAIL_SIZE);
+                let result = generate_thumbnail(&file_path, DEFAULT_THUMBNAIL_SIZE, &output);
                 (file_path, result)
             })
             .collect()
@@ -42,10 +51,15 @@ pub async fn get_native_thumbnails_batch(
 }
 
 #[command]
-pub async fn generate_thumbnail_command(file_path: String, size: Option<u32>) -> ThumbnailResult {
+pub async fn generate_thumbnail_command(
+    file_path: String,
+    size: Option<u32>,
+    output: Option<OutputOptions>,
+) -> ThumbnailResult {
     let target_size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+    let output = output.unwrap_or_default();
     let path = file_path.clone();
-    tokio::task::spawn_blocking(move || generate_thumbnail(&path, target_size))
+    tokio::task::spawn_blocking(move || generate_thumbnail(&path, target_size, &output))
         .await
         .unwrap_or_else(|_| ThumbnailResult {
             thumbnail_base64: None,
@@ -61,14 +75,16 @@ pub async fn generate_thumbnail_command(file_path: String, size: Option<u32>) ->
 pub async fn generate_thumbnails_batch_command(
     file_paths: Vec<String>,
     size: Option<u32>,
+    output: Option<OutputOptions>,
 ) -> HashMap<String, ThumbnailResult> {
     let target_size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+    let output = output.unwrap_or_default();
     let paths = file_paths.clone();
     tokio::task::spawn_blocking(move || {
         paths
             .into_iter()
             .map(|file_path| {
-                let result = generate_thumbnail(&file_path, target_size);
+                let result = generate_thumbnail(&file_path, target_size, &output);
                 (file_path, result)
             })
             .collect()
@@ -78,10 +94,17 @@ pub async fn generate_thumbnails_batch_command(
 }
 
 #[command]
-pub async fn generate_preview_command(file_path: String, size: Option<u32>) -> PreviewResult {
+pub async fn generate_preview_command(
+    file_path: String,
+    size: Option<u32>,
+    output: Option<OutputOptions>,
+    resize: Option<ResizeOptions>,
+) -> PreviewResult {
     let target_size = size.unwrap_or(DEFAULT_PREVIEW_SIZE);
+    let output = output.unwrap_or_default();
+    let resize = resize.unwrap_or_default();
     let path = file_path.clone();
-    tokio::task::spawn_blocking(move || generate_preview(&path, target_size))
+    tokio::task::spawn_blocking(move || generate_preview(&path, target_size, &output, &resize))
         .await
         .unwrap_or_else(|_| PreviewResult {
             preview_base64: None,
@@ -94,12 +117,14 @@ pub async fn generate_preview_command(file_path: String, size: Option<u32>) -> P
 
 #[command]
 pub async fn generate_video_thumbnail_command(
+    app: AppHandle,
     file_path: String,
     size: Option<u32>,
 ) -> ThumbnailResult {
     let target_size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+    let backend = app.state::<HardwareState>().backend();
     let path = file_path.clone();
-    tokio::task::spawn_blocking(move || generate_video_thumbnail(&path, target_size))
+    tokio::task::spawn_blocking(move || generate_video_thumbnail(&path, target_size, backend))
         .await
         .unwrap_or_else(|_| ThumbnailResult {
             thumbnail_base64: None,
@@ -112,10 +137,15 @@ pub async fn generate_video_thumbnail_command(
 }
 
 #[command]
-pub async fn generate_video_preview_command(file_path: String, size: Option<u32>) -> PreviewResult {
+pub async fn generate_video_preview_command(
+    app: AppHandle,
+    file_path: String,
+    size: Option<u32>,
+) -> PreviewResult {
     let target_size = size.unwrap_or(DEFAULT_PREVIEW_SIZE);
+    let backend = app.state::<HardwareState>().backend();
     let path = file_path.clone();
-    tokio::task::spawn_blocking(move || generate_video_preview(&path, target_size))
+    tokio::task::spawn_blocking(move || generate_video_preview(&path, target_size, backend))
         .await
         .unwrap_or_else(|_| PreviewResult {
             preview_base64: None,
@@ -125,3 +155,55 @@ pub async fn generate_video_preview_command(file_path: String, size: Option<u32>
             from_cache: false,
         })
 }
+
+#[command]
+pub async fn generate_video_keyframes(
+    app: AppHandle,
+    file_path: String,
+    count: Option<u32>,
+    size: Option<u32>,
+    selection: Option<KeyframeSelection>,
+    contact_sheet: Option<bool>,
+) -> Result<VideoKeyframesResult, String> {
+    let count = count.unwrap_or(DEFAULT_KEYFRAME_COUNT);
+    let target_size = size.unwrap_or(DEFAULT_KEYFRAME_SIZE);
+    let selection = selection.unwrap_or_default();
+    let contact_sheet = contact_sheet.unwrap_or(false);
+    let backend = app.state::<HardwareState>().backend();
+    tokio::task::spawn_blocking(move || {
+        extract_video_keyframes(
+            &file_path,
+            count,
+            target_size,
+            selection,
+            contact_sheet,
+            backend,
+        )
+    })
+    .await
+    .map_err(|e| format!("Keyframe extraction task failed: {}", e))?
+}
+
+#[command]
+pub async fn generate_video_scrub_strip_command(
+    app: AppHandle,
+    file_path: String,
+    frame_count: Option<u32>,
+    size: Option<u32>,
+) -> ScrubStripResult {
+    let frame_count = frame_count.unwrap_or(DEFAULT_SCRUB_FRAME_COUNT);
+    let target_size = size.unwrap_or(DEFAULT_SCRUB_FRAME_SIZE);
+    let backend = app.state::<HardwareState>().backend();
+    let path = file_path.clone();
+    tokio::task::spawn_blocking(move || {
+        generate_video_scrub_strip(&path, frame_count, target_size, backend)
+    })
+    .await
+    .unwrap_or(ScrubStripResult {
+        cache_path: None,
+        frame_count,
+        frame_width: None,
+        frame_height: None,
+        from_cache: false,
+    })
+}
@@ -0,0 +1,20 @@
+// Tauri command handlers for locating external tools (exiftool, ffmpeg, ffprobe)
+use crate::services::tools::{set_user_tool_path, tool_status, ExternalTool, ToolStatus};
+
+/// Report where each external tool was found, its version, and why any
+/// of them is missing
+#[tauri::command]
+pub async fn get_tool_status() -> Vec<ToolStatus> {
+    tokio::task::spawn_blocking(|| ExternalTool::ALL.iter().map(|t| tool_status(*t)).collect())
+        .await
+        .unwrap_or_default()
+}
+
+/// Point the app at a specific binary for `tool`; pass `null` to go back
+/// to automatic discovery
+#[tauri::command]
+pub async fn set_tool_path(tool: ExternalTool, path: Option<String>) -> Result<ToolStatus, String> {
+    tokio::task::spawn_blocking(move || set_user_tool_path(tool, path))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
@@ -7,13 +7,33 @@ mod models;
 mod services;
 
 // Import the command function for Tauri's generate_handler macro
+use commands::ai::{
+    generate_metadata, get_key_pool_status, get_price_table, get_rate_limit_status,
+    get_usage_report, list_ollama_models, set_price_table,
+};
 use commands::cache::{clear_cache_directory, get_cache_info};
-use commands::metadata::{embed_metadata, read_exif_metadata_command};
+use commands::hardware::{get_hardware_capabilities, set_force_cpu_video};
+use commands::jobs::{
+    cancel_batch_job, delete_batch_job, get_batch_job, list_batch_jobs, pause_batch_job,
+    resume_batch_job, start_batch_job,
+};
+use commands::metadata::{embed_metadata, read_exif_metadata_command, read_video_info};
+use commands::secrets::{delete_api_key, list_api_keys, store_api_key};
+use commands::templates::{
+    get_template_variables, render_prompt_template, validate_prompt_template,
+};
 use commands::thumbnail::{
     generate_preview_command, generate_thumbnail_command, generate_thumbnails_batch_command,
-    generate_video_preview_command, generate_video_thumbnail_command, get_native_thumbnail_command,
-    get_native_thumbnails_batch,
+    generate_video_keyframes, generate_video_preview_command, generate_video_scrub_strip_command,
+    generate_video_thumbnail_command, get_native_thumbnail_command, get_native_thumbnails_batch,
 };
+use commands::tools::{get_tool_status, set_tool_path};
+
+use services::gpu_thumbnail::HardwareState;
+use services::jobs::{JobManager, JOB_PROGRESS_EVENT};
+use services::thumbnail::get_ffmpeg_path;
+use services::tools;
+use tauri::{Emitter, Manager};
 
 // Re-export commonly used types for convenience
 pub use models::metadata::{EmbedMetadataRequest, EmbedMetadataResult};
@@ -29,9 +49,28 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_upload::init())
         .plugin(tauri_plugin_opener::init())
+        .setup(|app| {
+            // Bundled tools live in the resource dir, which is only known
+            // once the app exists, so tool lookup is initialised here.
+            tools::init(app.path().resource_dir().ok());
+
+            // Probe the GPU and ffmpeg once, in the background; video jobs read
+            // the result from state instead of shelling out to nvidia-smi/lsmod
+            // per frame.
+            app.manage(HardwareState::detect_in_background(get_ffmpeg_path));
+
+            // Batch jobs run on backend threads and report through events,
+            // so they outlive webview reloads.
+            let handle = app.handle().clone();
+            app.manage(JobManager::new(Box::new(move |event| {
+                let _ = handle.emit(JOB_PROGRESS_EVENT, event);
+            })));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             embed_metadata,
             read_exif_metadata_command,
+            read_video_info,
             get_native_thumbnail_command,
             get_native_thumbnails_batch,
             generate_thumbnail_command,
@@ -39,8 +78,34 @@ pub fn run() {
             generate_preview_command,
             generate_video_thumbnail_command,
             generate_video_preview_command,
+            generate_video_keyframes,
+            generate_video_scrub_strip_command,
             get_cache_info,
-            clear_cache_directory
+            clear_cache_directory,
+            get_hardware_capabilities,
+            set_force_cpu_video,
+            get_tool_status,
+            set_tool_path,
+            generate_metadata,
+            get_rate_limit_status,
+            get_key_pool_status,
+            get_usage_report,
+            get_price_table,
+            set_price_table,
+            list_ollama_models,
+            store_api_key,
+            list_api_keys,
+            delete_api_key,
+            validate_prompt_template,
+            get_template_variables,
+            render_prompt_template,
+            start_batch_job,
+            pause_batch_job,
+            resume_batch_job,
+            cancel_batch_job,
+            get_batch_job,
+            list_batch_jobs,
+            delete_batch_job
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,272 @@
+// Data structures for backend batch jobs
+use serde::{Deserialize, Serialize};
+
+use crate::services::ai::{GenerateOptions, GeneratedMetadata};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Cancelled,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Cancelled | JobStatus::Completed | JobStatus::Failed
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Pending,
+    Processing,
+    Completed,
+    Failed,
+    Skipped,
+}
+
+/// Pipeline steps a file (or, for export, a folder) goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    Thumbnail,
+    Generate,
+    Validate,
+    Embed,
+    Export,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataLimits {
+    #[serde(default = "default_title_limit")]
+    pub title_limit: usize,
+    #[serde(default = "default_description_limit")]
+    pub description_limit: usize,
+    #[serde(default = "default_keyword_limit")]
+    pub keyword_limit: usize,
+}
+
+fn default_title_limit() -> usize {
+    200
+}
+
+fn default_description_limit() -> usize {
+    200
+}
+
+fn default_keyword_limit() -> usize {
+    50
+}
+
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        Self {
+            title_limit: default_title_limit(),
+            description_limit: default_description_limit(),
+            keyword_limit: default_keyword_limit(),
+        }
+    }
+}
+
+/// Which generated fields are written into the file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EmbedFields {
+    pub title: bool,
+    pub description: bool,
+    pub keywords: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportPlatform {
+    AdobeStock,
+    Shutterstock,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSettings {
+    pub platform: ExportPlatform,
+    /// Directory the per-folder CSV files are written to
+    pub directory: String,
+    #[serde(default)]
+    pub adobe_category: Option<String>,
+    #[serde(default)]
+    pub shutterstock_category1: Option<String>,
+    #[serde(default)]
+    pub shutterstock_category2: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderJobSpec {
+    pub folder_path: String,
+    /// Files to process; when empty the folder is scanned for media files
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Rendered prompt for this folder's template; falls back to `ai.prompt`
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Template rendered per file for this folder; takes precedence over
+    /// `prompt` and `ai.prompt_template`
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Everything needed to run (and later resume) a batch job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJobSpec {
+    pub folders: Vec<FolderJobSpec>,
+    pub ai: GenerateOptions,
+    #[serde(default)]
+    pub limits: MetadataLimits,
+    #[serde(default = "default_true")]
+    pub generate_thumbnails: bool,
+    /// `None` skips embedding
+    #[serde(default)]
+    pub embed: Option<EmbedFields>,
+    /// `None` skips CSV export
+    #[serde(default)]
+    pub export: Option<ExportSettings>,
+    /// Files processed at once within a folder
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Pause between files on each worker, for rate-limited keys
+    #[serde(default)]
+    pub request_delay_ms: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileJobState {
+    pub file_path: String,
+    pub status: FileStatus,
+    /// Stage currently running, or the one that failed
+    pub stage: Option<JobStage>,
+    pub metadata: Option<GeneratedMetadata>,
+    /// Limit violations found by the validate stage; they don't fail the file
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub embedded: bool,
+    /// Metadata came from the response cache rather than a new request
+    #[serde(default)]
+    pub cached: bool,
+}
+
+impl FileJobState {
+    pub fn new(file_path: String) -> Self {
+        Self {
+            file_path,
+            status: FileStatus::Pending,
+            stage: None,
+            metadata: None,
+            warnings: Vec::new(),
+            error: None,
+            embedded: false,
+            cached: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderJobState {
+    pub folder_path: String,
+    pub folder_name: String,
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    /// False until the file list has been resolved (scanned if needed)
+    #[serde(default)]
+    pub scanned: bool,
+    pub files: Vec<FileJobState>,
+    pub export_path: Option<String>,
+    /// Why the folder could not be scanned or exported
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Full job state; this is what gets checkpointed to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub id: String,
+    pub status: JobStatus,
+    pub spec: BatchJobSpec,
+    pub folders: Vec<FolderJobState>,
+    pub error: Option<String>,
+    /// Unix milliseconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl BatchJob {
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let files = self.folders.iter().flat_map(|f| f.files.iter());
+        let (mut total, mut completed, mut failed) = (0, 0, 0);
+        for file in files {
+            total += 1;
+            match file.status {
+                FileStatus::Completed | FileStatus::Skipped => completed += 1,
+                FileStatus::Failed => failed += 1,
+                _ => {}
+            }
+        }
+        (total, completed, failed)
+    }
+
+    pub fn summary(&self) -> BatchJobSummary {
+        let (total_files, completed_files, failed_files) = self.counts();
+        BatchJobSummary {
+            id: self.id.clone(),
+            status: self.status,
+            folder_count: self.folders.len(),
+            total_files,
+            completed_files,
+            failed_files,
+            error: self.error.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJobSummary {
+    pub id: String,
+    pub status: JobStatus,
+    pub folder_count: usize,
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub failed_files: usize,
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Payload of the `batch-job-progress` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobProgressEvent {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub folder_index: Option<usize>,
+    pub file_path: Option<String>,
+    pub stage: Option<JobStage>,
+    pub file_status: Option<FileStatus>,
+    pub metadata: Option<GeneratedMetadata>,
+    pub error: Option<String>,
+    /// CSV written by the export stage, on folder-level events
+    pub export_path: Option<String>,
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub failed_files: usize,
+}
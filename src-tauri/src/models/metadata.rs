@@ -17,10 +17,24 @@ pub struct EmbedMetadataResult {
 }
 
 // Data structure for reading EXIF metadata
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ExifData {
     pub file_path: String,
     pub title: Option<String>,
     pub description: Option<String>,
     pub keywords: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    /// `YYYY-MM-DD HH:MM:SS` as recorded by the camera (local time)
+    pub capture_date: Option<String>,
+    /// Signed decimal degrees
+    pub gps_latitude: Option<f64>,
+    pub gps_longitude: Option<f64>,
+    /// IPTC/XMP location fields; cameras don't geocode, so these are only
+    /// set when a photo manager or the photographer filled them in
+    pub sublocation: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
 }
@@ -0,0 +1,118 @@
+// On-disk cache of model replies.  The key covers everything that shapes
+// the reply -- the prepared image bytes, the rendered prompt, the model and
+// the sampling parameters -- so re-running a folder only pays for the files
+// whose request actually changed.  Like the thumbnail cache, entries are
+// named by their blake3 key and never need invalidating.
+use blake3::Hasher;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::usage::TokenUsage;
+use super::{AiRequest, AiResponse, ProviderKind};
+
+/// Bump when the key layout or entry format changes
+const CACHE_VERSION: &str = "v1";
+
+/// A reply as stored on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    text: String,
+    model: String,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+    /// Unix milliseconds
+    created_at: u64,
+}
+
+pub fn get_ai_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("descify").join("ai_responses"))
+}
+
+/// Key for `request` sent to `provider` at `base_url`.  The endpoint is
+/// part of the key because two local servers can serve different weights
+/// under the same model name.
+pub fn compute_cache_key(
+    provider: ProviderKind,
+    base_url: Option<&str>,
+    request: &AiRequest,
+) -> String {
+    let schema = request
+        .json_schema
+        .as_ref()
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+
+    let mut hasher = Hasher::new();
+    for part in [
+        CACHE_VERSION,
+        &provider.to_string(),
+        base_url.unwrap_or_default(),
+        &request.model,
+        &request.max_tokens.to_string(),
+        &request.temperature.to_bits().to_string(),
+        &schema,
+        request.image.mime_type,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update(b"|".as_ref());
+    }
+    // Lengths first so the variable-length parts can't run into each other.
+    hasher.update(request.prompt.len().to_string().as_bytes());
+    hasher.update(b"|".as_ref());
+    hasher.update(request.prompt.as_bytes());
+    hasher.update(&request.image.data);
+    hasher.finalize().to_hex().to_string()
+}
+
+fn entry_path(cache_key: &str) -> Option<PathBuf> {
+    get_ai_cache_dir().map(|d| d.join(format!("{}.json", cache_key)))
+}
+
+/// The cached reply for `cache_key`.  Unreadable entries count as misses.
+pub fn get_cached_response(cache_key: &str) -> Option<AiResponse> {
+    let data = fs::read(entry_path(cache_key)?).ok()?;
+    let cached: CachedResponse = serde_json::from_slice(&data).ok()?;
+    Some(AiResponse {
+        text: cached.text,
+        model: cached.model,
+        usage: cached.usage,
+    })
+}
+
+/// Store `response` under `cache_key`.  A failed write only costs a repeat
+/// request later, so errors are logged rather than returned.
+pub fn save_response_to_cache(cache_key: &str, response: &AiResponse) {
+    let Some(path) = entry_path(cache_key) else {
+        return;
+    };
+    let cached = CachedResponse {
+        text: response.text.clone(),
+        model: response.model.clone(),
+        usage: response.usage,
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    };
+    let result = serde_json::to_vec(&cached)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            // Write then rename, so a concurrent worker never reads half an
+            // entry.  The temp name is unique per write because workers in
+            // one process can store the same key at once.
+            let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+            fs::write(&tmp, data).map_err(|e| e.to_string())?;
+            fs::rename(&tmp, &path).map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                e.to_string()
+            })
+        });
+    if let Err(e) = result {
+        eprintln!("[ai] Failed to cache response in {:?}: {}", path, e);
+    }
+}
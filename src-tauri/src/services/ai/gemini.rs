@@ -0,0 +1,329 @@
+// Google Gemini `generateContent` provider
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::usage::TokenUsage;
+use super::{
+    error_from_response, http_client, is_schema_rejection, AiError, AiProvider, AiRequest,
+    AiResponse, ProviderKind,
+};
+
+pub const DEFAULT_GEMINI_MODEL: &str = "gemini-2.0-flash-lite";
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+pub struct GeminiProvider {
+    api_key: String,
+    base_url: String,
+    client: reqwest::blocking::Client,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(default)]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(default)]
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+    /// Thinking models bill reasoning tokens as output
+    #[serde(default)]
+    thoughts_token_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<Content>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Part {
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
+impl GeminiProvider {
+    /// `base_url` overrides the public endpoint, e.g. for a proxy or a mock
+    /// server; it should include the API version (`.../v1beta`).
+    pub fn new(api_key: String, base_url: Option<String>) -> Result<Self, AiError> {
+        Ok(Self {
+            api_key,
+            base_url: base_url.unwrap_or_else(|| GEMINI_BASE_URL.to_string()),
+            client: http_client()?,
+        })
+    }
+
+    /// Model ids from the models list come back as `models/gemini-...`.
+    fn model_id(model: &str) -> &str {
+        model.strip_prefix("models/").unwrap_or(model)
+    }
+
+    fn send(
+        &self,
+        url: &str,
+        payload: &Value,
+        model: &str,
+    ) -> Result<GenerateContentResponse, AiError> {
+        // The key goes in a header rather than `?key=` so it never shows up
+        // in URLs that end up in logs or error messages.
+        let response = self
+            .client
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .json(payload)
+            .send()?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response, model));
+        }
+
+        response
+            .json()
+            .map_err(|e| AiError::InvalidResponse(e.to_string()))
+    }
+}
+
+/// Gemini's `responseSchema` is an OpenAPI subset: upper-case type names
+/// and no `additionalProperties`.
+fn gemini_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| k.as_str() != "additionalProperties")
+                .map(|(k, v)| match (k.as_str(), v) {
+                    ("type", Value::String(t)) => (k.clone(), Value::String(t.to_uppercase())),
+                    ("properties", Value::Object(props)) => (
+                        k.clone(),
+                        Value::Object(
+                            props
+                                .iter()
+                                .map(|(name, p)| (name.clone(), gemini_schema(p)))
+                                .collect(),
+                        ),
+                    ),
+                    ("items", item) => (k.clone(), gemini_schema(item)),
+                    _ => (k.clone(), v.clone()),
+                })
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+impl AiProvider for GeminiProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Google
+    }
+
+    fn generate(&self, request: &AiRequest) -> Result<AiResponse, AiError> {
+        let model = Self::model_id(&request.model);
+        let url = format!("{}/models/{}:generateContent", self.base_url, model);
+
+        let mut payload = json!({
+            "contents": [{
+                "role": "user",
+                "parts": [
+                    { "text": request.prompt },
+                    {
+                        "inline_data": {
+                            "mime_type": request.image.mime_type,
+                            "data": request.image.base64(),
+                        }
+                    }
+                ]
+            }],
+            "generationConfig": {
+                "maxOutputTokens": request.max_tokens,
+                "temperature": request.temperature,
+            }
+        });
+
+        if let Some(ref schema) = request.json_schema {
+            payload["generationConfig"]["responseMimeType"] = json!("application/json");
+            payload["generationConfig"]["responseSchema"] = gemini_schema(schema);
+        }
+
+        let body = match self.send(&url, &payload, model) {
+            // Older models reject JSON mode; the parser copes with plain text.
+            Err(e) if request.json_schema.is_some() && is_schema_rejection(&e) => {
+                let config = &mut payload["generationConfig"];
+                if let Some(config) = config.as_object_mut() {
+                    config.remove("responseMimeType");
+                    config.remove("responseSchema");
+                }
+                self.send(&url, &payload, model)?
+            }
+            result => result?,
+        };
+
+        let usage = body.usage_metadata.map(|u| TokenUsage {
+            input_tokens: u.prompt_token_count,
+            output_tokens: u.candidates_token_count + u.thoughts_token_count,
+            reported_cost: None,
+        });
+
+        if let Some(reason) = body.prompt_feedback.and_then(|f| f.block_reason) {
+            return Err(AiError::InvalidResponse(format!(
+                "Gemini blocked the request ({})",
+                reason
+            )));
+        }
+
+        let candidate = body
+            .candidates
+            .into_iter()
+            .next()
+            .ok_or_else(|| AiError::InvalidResponse("Gemini returned no candidates".into()))?;
+        let text: String = candidate
+            .content
+            .map(|c| c.parts.into_iter().filter_map(|p| p.text).collect())
+            .unwrap_or_default();
+
+        if text.trim().is_empty() {
+            return Err(AiError::InvalidResponse(format!(
+                "Gemini returned an empty response (finish reason: {})",
+                candidate.finish_reason.as_deref().unwrap_or("unknown")
+            )));
+        }
+
+        Ok(AiResponse {
+            text,
+            model: model.to_string(),
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai::stub_server::{test_request, StubResponse, StubServer};
+
+    const REPLY: &str = r#"{
+        "candidates": [{
+            "content": { "parts": [{ "text": "{\"title\":" }, { "text": "\"Sunset\"}" }] },
+            "finishReason": "STOP"
+        }],
+        "usageMetadata": {
+            "promptTokenCount": 1200,
+            "candidatesTokenCount": 80,
+            "thoughtsTokenCount": 40
+        }
+    }"#;
+
+    fn provider(server: &StubServer) -> GeminiProvider {
+        GeminiProvider::new("test-key".into(), Some(server.url.clone())).unwrap()
+    }
+
+    #[test]
+    fn joins_parts_and_counts_thoughts_as_output() {
+        let server = StubServer::start(vec![StubResponse::json(200, REPLY)]);
+        let response = provider(&server)
+            .generate(&test_request("models/gemini-2.0-flash"))
+            .unwrap();
+
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+        assert_eq!(response.model, "gemini-2.0-flash");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.input_tokens, 1200);
+        assert_eq!(usage.output_tokens, 120);
+        assert_eq!(usage.reported_cost, None);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/models/gemini-2.0-flash:generateContent");
+        assert_eq!(requests[0].header("x-goog-api-key"), Some("test-key"));
+        let config = &requests[0].json()["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(config["responseSchema"]["type"], "OBJECT");
+    }
+
+    #[test]
+    fn retries_without_schema_when_rejected() {
+        let server = StubServer::start(vec![
+            StubResponse::json(
+                400,
+                r#"{"error":{"message":"Invalid JSON payload: unknown field responseSchema"}}"#,
+            ),
+            StubResponse::json(200, REPLY),
+        ]);
+        let response = provider(&server)
+            .generate(&test_request("gemini-1.0-pro-vision"))
+            .unwrap();
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let retry = &requests[1].json()["generationConfig"];
+        assert!(retry.get("responseMimeType").is_none());
+        assert!(retry.get("responseSchema").is_none());
+        assert_eq!(retry["maxOutputTokens"], 200);
+    }
+
+    #[test]
+    fn maps_error_statuses_to_http_errors() {
+        let server = StubServer::start(vec![
+            StubResponse::json(429, r#"{"error":{"message":"Resource exhausted"}}"#)
+                .with_header("Retry-After", "7"),
+            StubResponse::text(503, "overloaded"),
+        ]);
+        let provider = provider(&server);
+
+        match provider.generate(&test_request("gemini-2.0-flash")) {
+            Err(AiError::Http {
+                status,
+                body,
+                retry_after,
+            }) => {
+                assert_eq!(status, 429);
+                assert!(body.contains("Resource exhausted"));
+                assert_eq!(retry_after, Some(std::time::Duration::from_secs(7)));
+            }
+            other => panic!("expected a 429, got {:?}", other),
+        }
+        match provider.generate(&test_request("gemini-2.0-flash")) {
+            Err(AiError::Http { status, body, .. }) => {
+                assert_eq!(status, 503);
+                assert_eq!(body, "overloaded");
+            }
+            other => panic!("expected a 503, got {:?}", other),
+        }
+        // Neither error looks like a schema rejection, so neither is retried.
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn reports_blocked_prompts() {
+        let server = StubServer::start(vec![StubResponse::json(
+            200,
+            r#"{"promptFeedback":{"blockReason":"SAFETY"},"usageMetadata":{"promptTokenCount":900}}"#,
+        )]);
+        match provider(&server).generate(&test_request("gemini-2.0-flash")) {
+            Err(AiError::InvalidResponse(msg)) => assert!(msg.contains("SAFETY")),
+            other => panic!("expected a blocked prompt, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+// Round-robin rotation across several API keys for one provider.  A key
+// that hits its quota is parked until it is likely to have reset, a key the
+// provider rejects is dropped for the rest of the session, and the next
+// request simply moves on to the following key.
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::rate_limit::key_id;
+use super::{AiError, ProviderKind};
+
+lazy_static! {
+    pub static ref KEY_POOL: KeyPool = KeyPool::new();
+}
+
+/// How long an exhausted key is skipped when the provider doesn't say.
+/// Daily quotas reset at different times per provider, so re-probe hourly
+/// rather than guessing.
+const EXHAUSTED_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyHealth {
+    Active,
+    /// Out of quota; tried again once the cooldown passes
+    Exhausted,
+    /// Rejected by the provider; skipped until the app restarts
+    Invalid,
+}
+
+/// What a failed request says about the key that sent it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFailure {
+    Exhausted,
+    Invalid,
+}
+
+/// Classify provider errors that are the key's fault rather than the
+/// request's.  Plain 429s are rate limiting and stay with the rate limiter;
+/// only quota messages take a key out of rotation.
+pub fn classify_error(error: &AiError) -> Option<KeyFailure> {
+    let AiError::Http { status, body, .. } = error else {
+        return None;
+    };
+    let lower = body.to_lowercase();
+    match status {
+        401 | 403 => Some(KeyFailure::Invalid),
+        400 if lower.contains("api_key_invalid") || lower.contains("api key not valid") => {
+            Some(KeyFailure::Invalid)
+        }
+        402 => Some(KeyFailure::Exhausted),
+        429 if lower.contains("insufficient_quota")
+            || lower.contains("perday")
+            || lower.contains("per day")
+            || lower.contains("daily")
+            || lower.contains("credits") =>
+        {
+            Some(KeyFailure::Exhausted)
+        }
+        _ => None,
+    }
+}
+
+struct KeyState {
+    health: KeyHealth,
+    available_at: Option<Instant>,
+    requests: u64,
+    successes: u64,
+    failures: u64,
+    last_error: Option<String>,
+    last_used_at: Option<String>,
+}
+
+impl KeyState {
+    fn new() -> Self {
+        Self {
+            health: KeyHealth::Active,
+            available_at: None,
+            requests: 0,
+            successes: 0,
+            failures: 0,
+            last_error: None,
+            last_used_at: None,
+        }
+    }
+
+    fn is_available(&mut self, now: Instant) -> bool {
+        match self.health {
+            KeyHealth::Active => true,
+            KeyHealth::Invalid => false,
+            KeyHealth::Exhausted => {
+                if self.available_at.is_some_and(|at| at <= now) {
+                    self.health = KeyHealth::Active;
+                    self.available_at = None;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct ProviderPool {
+    next: usize,
+    keys: HashMap<String, KeyState>,
+}
+
+/// Snapshot of one key for `get_key_pool_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPoolStatus {
+    pub provider: ProviderKind,
+    /// Short hash of the key, never the key itself
+    pub key_id: String,
+    pub health: KeyHealth,
+    /// Time left before an exhausted key is tried again, in milliseconds
+    pub available_in_ms: Option<u64>,
+    pub requests: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+pub struct KeyPool {
+    pools: Mutex<HashMap<ProviderKind, ProviderPool>>,
+}
+
+impl KeyPool {
+    fn new() -> Self {
+        Self {
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<ProviderKind, ProviderPool>> {
+        self.pools.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Pick the next usable key after the last one handed out.  Returns
+    /// `None` when every key is exhausted or invalid.
+    pub fn next_key<'a>(&self, provider: ProviderKind, keys: &'a [String]) -> Option<&'a str> {
+        if keys.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut pools = self.lock();
+        let pool = pools.entry(provider).or_default();
+        for offset in 0..keys.len() {
+            let index = (pool.next + offset) % keys.len();
+            let key = &keys[index];
+            let state = pool
+                .keys
+                .entry(key_id(Some(key)))
+                .or_insert_with(KeyState::new);
+            if state.is_available(now) {
+                state.requests += 1;
+                state.last_used_at = Some(chrono::Utc::now().to_rfc3339());
+                pool.next = index + 1;
+                return Some(key);
+            }
+        }
+        None
+    }
+
+    pub fn record_success(&self, provider: ProviderKind, key: &str) {
+        self.with_key(provider, key, |state| state.successes += 1);
+    }
+
+    /// Count a failure against `key`, taking it out of rotation when the
+    /// error says the key itself is the problem.
+    pub fn record_failure(&self, provider: ProviderKind, key: &str, error: &AiError) {
+        let failure = classify_error(error);
+        let retry_after = match error {
+            AiError::Http { retry_after, .. } => *retry_after,
+            _ => None,
+        };
+        self.with_key(provider, key, |state| {
+            state.failures += 1;
+            state.last_error = Some(error.to_string());
+            match failure {
+                Some(KeyFailure::Invalid) => state.health = KeyHealth::Invalid,
+                Some(KeyFailure::Exhausted) => {
+                    state.health = KeyHealth::Exhausted;
+                    state.available_at =
+                        Some(Instant::now() + retry_after.unwrap_or(EXHAUSTED_COOLDOWN));
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn with_key(&self, provider: ProviderKind, key: &str, f: impl FnOnce(&mut KeyState)) {
+        let mut pools = self.lock();
+        let state = pools
+            .entry(provider)
+            .or_default()
+            .keys
+            .entry(key_id(Some(key)))
+            .or_insert_with(KeyState::new);
+        f(state);
+    }
+
+    pub fn status(&self) -> Vec<KeyPoolStatus> {
+        let now = Instant::now();
+        let mut pools = self.lock();
+        let mut statuses: Vec<KeyPoolStatus> = pools
+            .iter_mut()
+            .flat_map(|(provider, pool)| {
+                pool.keys.iter_mut().map(move |(id, state)| {
+                    state.is_available(now);
+                    KeyPoolStatus {
+                        provider: *provider,
+                        key_id: id.clone(),
+                        health: state.health,
+                        available_in_ms: state
+                            .available_at
+                            .map(|at| at.saturating_duration_since(now).as_millis() as u64),
+                        requests: state.requests,
+                        successes: state.successes,
+                        failures: state.failures,
+                        last_error: state.last_error.clone(),
+                        last_used_at: state.last_used_at.clone(),
+                    }
+                })
+            })
+            .collect();
+        statuses.sort_by(|a, b| {
+            (a.provider.to_string(), &a.key_id).cmp(&(b.provider.to_string(), &b.key_id))
+        });
+        statuses
+    }
+}
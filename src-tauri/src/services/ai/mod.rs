@@ -0,0 +1,606 @@
+// AI metadata generation.  Providers talk to the vision APIs directly from
+// the backend so batches keep running when the webview is throttled and API
+// keys never have to round-trip through JS.
+pub mod cache;
+pub mod gemini;
+pub mod key_pool;
+pub mod ollama;
+pub mod openai;
+pub mod parser;
+pub mod rate_limit;
+#[cfg(test)]
+mod stub_server;
+pub mod usage;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::models::jobs::MetadataLimits;
+use crate::services::gpu_thumbnail::VideoBackend;
+use crate::services::process::CancelToken;
+use crate::services::secrets;
+use crate::services::template::{self, TemplateContext};
+use crate::services::thumbnail::{
+    generate_preview, generate_video_preview, is_video_file, OutputOptions, PreviewResult,
+    ResizeOptions,
+};
+
+use key_pool::KEY_POOL;
+pub use parser::GeneratedMetadata;
+use rate_limit::RATE_LIMITER;
+use usage::{TokenUsage, UsageRecord};
+
+/// Long edge of the image sent to the model.  Vision models downscale
+/// anything larger, so bigger images only cost upload time and tokens.
+pub const DEFAULT_AI_IMAGE_SIZE: u32 = 480;
+const DEFAULT_MAX_TOKENS: u32 = 1000;
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Which API a request goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Google,
+    Openai,
+    Openrouter,
+    /// Any OpenAI-compatible local server (LM Studio, llama.cpp, vLLM...)
+    Local,
+    /// Ollama's native API
+    Ollama,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 5] = [
+        ProviderKind::Google,
+        ProviderKind::Openai,
+        ProviderKind::Openrouter,
+        ProviderKind::Local,
+        ProviderKind::Ollama,
+    ];
+
+    /// Whether requests ask for JSON-schema output by default.  OpenRouter
+    /// and OpenAI-compatible local servers depend on the model, so they opt
+    /// in per request; Ollama constrains any model to the schema.
+    pub fn supports_json_schema(&self) -> bool {
+        matches!(
+            self,
+            ProviderKind::Google | ProviderKind::Openai | ProviderKind::Ollama
+        )
+    }
+
+    pub fn default_model(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::Google => Some(gemini::DEFAULT_GEMINI_MODEL),
+            ProviderKind::Openai => Some(openai::DEFAULT_OPENAI_MODEL),
+            ProviderKind::Openrouter => Some(openai::DEFAULT_OPENROUTER_MODEL),
+            ProviderKind::Local | ProviderKind::Ollama => None,
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProviderKind::Google => "google",
+            ProviderKind::Openai => "openai",
+            ProviderKind::Openrouter => "openrouter",
+            ProviderKind::Local => "local",
+            ProviderKind::Ollama => "ollama",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Encoded image ready to be attached to a request
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    pub mime_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+impl PreparedImage {
+    pub fn base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.base64())
+    }
+}
+
+/// A single vision request: one prompt and one image
+#[derive(Debug, Clone)]
+pub struct AiRequest {
+    pub model: String,
+    pub prompt: String,
+    pub image: PreparedImage,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    /// Ask for output matching this JSON schema using the provider's native
+    /// structured output mode
+    pub json_schema: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AiResponse {
+    pub text: String,
+    pub model: String,
+    /// Token counts, when the provider reports them
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug)]
+pub enum AiError {
+    /// Provider settings are incomplete (missing key, model or URL)
+    Config(String),
+    /// The image could not be prepared for upload
+    Image(String),
+    /// The request never got an HTTP response
+    Network(String),
+    /// The provider answered with a non-success status
+    Http {
+        status: u16,
+        body: String,
+        /// Parsed `Retry-After` header, if the provider sent one
+        retry_after: Option<Duration>,
+    },
+    /// The model rejected the image input
+    UnsupportedModel(String),
+    /// The response body was not what the API documents
+    InvalidResponse(String),
+    /// The model's text could not be turned into metadata
+    Parse(String),
+    /// The caller cancelled while the request was waiting to be sent
+    Cancelled,
+}
+
+impl fmt::Display for AiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AiError::Config(msg) => write!(f, "{}", msg),
+            AiError::Image(msg) => write!(f, "Failed to prepare image: {}", msg),
+            AiError::Network(msg) => write!(f, "Network error: {}", msg),
+            AiError::Http { status, body, .. } => write!(f, "API Error {}: {}", status, body),
+            AiError::UnsupportedModel(model) => write!(
+                f,
+                "The selected model ({}) does not support image input. Please select a vision-capable model from Settings.",
+                model
+            ),
+            AiError::InvalidResponse(msg) => write!(f, "Unexpected API response: {}", msg),
+            AiError::Parse(msg) => write!(f, "Failed to parse AI response: {}", msg),
+            AiError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AiError {}
+
+impl From<reqwest::Error> for AiError {
+    fn from(e: reqwest::Error) -> Self {
+        AiError::Network(e.to_string())
+    }
+}
+
+/// Parsed metadata plus which provider and model produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataResult {
+    #[serde(flatten)]
+    pub metadata: GeneratedMetadata,
+    pub provider: ProviderKind,
+    pub model: String,
+    /// Limit violations; the metadata is still usable
+    pub warnings: Vec<String>,
+    pub usage: Option<TokenUsage>,
+    /// Cost of this request in USD, if it could be priced
+    pub cost_usd: Option<f64>,
+    /// Served from the response cache without calling the provider
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// A vision-capable text generation API
+pub trait AiProvider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+
+    fn generate(&self, request: &AiRequest) -> Result<AiResponse, AiError>;
+}
+
+/// Options for `generate_metadata`, mirroring the settings the UI exposes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateOptions {
+    pub provider: ProviderKind,
+    /// Falls back to the provider's default model
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Overrides the provider's endpoint; required for `local`.  For
+    /// `ollama` this is the server root (`http://localhost:11434`).
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Fully rendered prompt text; ignored when `prompt_template` is set
+    #[serde(default)]
+    pub prompt: String,
+    /// Template rendered per file with its EXIF data and the limits below
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    #[serde(default)]
+    pub image_size: Option<u32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Keyword count checked after parsing, like the lengths below
+    #[serde(default)]
+    pub keyword_limit: Option<usize>,
+    /// Lengths checked after parsing; violations become warnings
+    #[serde(default)]
+    pub title_limit: Option<usize>,
+    #[serde(default)]
+    pub description_limit: Option<usize>,
+    /// Request JSON-schema output; defaults to what the provider supports
+    #[serde(default)]
+    pub structured_output: Option<bool>,
+    /// Send the request even when an identical one is in the response cache
+    #[serde(default)]
+    pub force_refresh: bool,
+    /// How long Ollama keeps the model loaded after a request: a duration
+    /// (`10m`, `1h`) or seconds, `-1` for indefinitely.  Unset uses the
+    /// server's default.
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+}
+
+impl GenerateOptions {
+    /// Limits for validation and templates; unset ones use the defaults
+    pub fn limits(&self) -> MetadataLimits {
+        let defaults = MetadataLimits::default();
+        MetadataLimits {
+            title_limit: self.title_limit.unwrap_or(defaults.title_limit),
+            description_limit: self.description_limit.unwrap_or(defaults.description_limit),
+            keyword_limit: self.keyword_limit.unwrap_or(parser::DEFAULT_KEYWORD_LIMIT),
+        }
+    }
+
+    /// The keys stored with `store_api_key` for the provider; requests
+    /// rotate across them, skipping any that run out of quota.  May block
+    /// on the keyring, so resolve it once per request.
+    pub fn key_pool(&self) -> Vec<String> {
+        secrets::api_keys(self.provider).unwrap_or_else(|e| {
+            eprintln!("[ai] {}", e);
+            Vec::new()
+        })
+    }
+}
+
+/// Shared blocking HTTP client; providers run inside `spawn_blocking`.
+pub(crate) fn http_client() -> Result<reqwest::blocking::Client, AiError> {
+    reqwest::blocking::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(AiError::from)
+}
+
+/// Turns a non-success response into an [`AiError`], recognising the
+/// "model can't see images" family of 400s the providers return.
+pub(crate) fn error_from_response(response: reqwest::blocking::Response, model: &str) -> AiError {
+    let status = response.status().as_u16();
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(rate_limit::parse_retry_after);
+    let body = response.text().unwrap_or_default();
+    let lower = body.to_lowercase();
+    let image_unsupported = lower.contains("does not support image")
+        || lower.contains("image input")
+        || lower.contains("image modality")
+        || lower.contains("media type");
+    if status == 400 && image_unsupported {
+        return AiError::UnsupportedModel(model.to_string());
+    }
+    AiError::Http {
+        status,
+        body,
+        retry_after,
+    }
+}
+
+/// A 400 complaining about `response_format` or the schema: the model
+/// can't do structured output, so the request is worth resending without.
+pub(crate) fn is_schema_rejection(error: &AiError) -> bool {
+    let AiError::Http {
+        status: 400, body, ..
+    } = error
+    else {
+        return false;
+    };
+    let lower = body.to_lowercase();
+    [
+        "response_format",
+        "json_schema",
+        "response_schema",
+        "responseschema",
+        "json mode",
+        "response_mime_type",
+        "responsemimetype",
+    ]
+    .iter()
+    .any(|hint| lower.contains(hint))
+}
+
+/// Strips trailing slashes so `{base}/path` never doubles up.
+pub(crate) fn normalize_base_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// Build the provider described by `options`, using the first key of its
+/// pool.
+pub fn create_provider(options: &GenerateOptions) -> Result<Box<dyn AiProvider>, AiError> {
+    build_provider(options, options.key_pool().into_iter().next())
+}
+
+fn build_provider(
+    options: &GenerateOptions,
+    api_key: Option<String>,
+) -> Result<Box<dyn AiProvider>, AiError> {
+    let base_url = options
+        .base_url
+        .as_deref()
+        .map(normalize_base_url)
+        .filter(|u| !u.is_empty());
+
+    let provider: Box<dyn AiProvider> = match options.provider {
+        ProviderKind::Google => {
+            let key = api_key.ok_or_else(|| {
+                AiError::Config("No API key stored for google. Add one in Settings.".to_string())
+            })?;
+            Box::new(gemini::GeminiProvider::new(key, base_url)?)
+        }
+        ProviderKind::Openai | ProviderKind::Openrouter => {
+            let key = api_key.ok_or_else(|| {
+                AiError::Config(format!(
+                    "No API key stored for {}. Add one in Settings.",
+                    options.provider
+                ))
+            })?;
+            Box::new(openai::OpenAiCompatibleProvider::new(
+                options.provider,
+                Some(key),
+                base_url,
+            )?)
+        }
+        ProviderKind::Local => {
+            if base_url.is_none() {
+                return Err(AiError::Config(
+                    "No local AI server URL configured. Please set it in Settings.".to_string(),
+                ));
+            }
+            Box::new(openai::OpenAiCompatibleProvider::new(
+                ProviderKind::Local,
+                api_key,
+                base_url,
+            )?)
+        }
+        ProviderKind::Ollama => Box::new(ollama::OllamaProvider::new(
+            api_key,
+            base_url,
+            options.keep_alive.as_deref(),
+        )?),
+    };
+    Ok(provider)
+}
+
+fn read_preview_bytes(result: PreviewResult) -> Option<Vec<u8>> {
+    if let Some(path) = result.cache_path {
+        return fs::read(path).ok();
+    }
+    result
+        .preview_base64
+        .and_then(|b64| base64::engine::general_purpose::STANDARD.decode(b64).ok())
+}
+
+/// Produce the JPEG sent to the model, reusing the cached preview pipeline
+/// (orientation, colour management, video frame scoring).
+pub fn prepare_image(
+    file_path: &str,
+    target_size: u32,
+    backend: VideoBackend,
+) -> Result<PreparedImage, AiError> {
+    let path = Path::new(file_path);
+    if !path.is_file() {
+        return Err(AiError::Image(format!(
+            "File does not exist: {}",
+            file_path
+        )));
+    }
+
+    let result = if is_video_file(path) {
+        generate_video_preview(file_path, target_size, backend)
+    } else {
+        generate_preview(
+            file_path,
+            target_size,
+            &OutputOptions::default(),
+            &ResizeOptions::default(),
+        )
+    };
+
+    let data = read_preview_bytes(result)
+        .ok_or_else(|| AiError::Image(format!("Could not decode {}", file_path)))?;
+    Ok(PreparedImage {
+        mime_type: "image/jpeg",
+        data,
+    })
+}
+
+/// Send `request` with the next usable key from `keys`, moving on to the
+/// following key whenever one turns out to be exhausted or invalid.
+fn generate_with_pool(
+    options: &GenerateOptions,
+    keys: &[String],
+    request: &AiRequest,
+    cancel: Option<&CancelToken>,
+) -> Result<AiResponse, AiError> {
+    if keys.is_empty() {
+        // Keyless providers (local servers) skip the pool entirely.
+        let provider = build_provider(options, None)?;
+        return RATE_LIMITER.execute(provider.kind(), None, cancel, || provider.generate(request));
+    }
+
+    let mut last_error = None;
+    for _ in 0..keys.len() {
+        let Some(key) = KEY_POOL.next_key(options.provider, keys) else {
+            break;
+        };
+        let provider = build_provider(options, Some(key.to_string()))?;
+        match RATE_LIMITER.execute(provider.kind(), Some(key), cancel, || {
+            provider.generate(request)
+        }) {
+            Ok(response) => {
+                KEY_POOL.record_success(options.provider, key);
+                return Ok(response);
+            }
+            Err(e) => {
+                KEY_POOL.record_failure(options.provider, key, &e);
+                if key_pool::classify_error(&e).is_none() {
+                    return Err(e);
+                }
+                eprintln!(
+                    "[ai] {} key {} taken out of rotation: {}",
+                    options.provider,
+                    rate_limit::key_id(Some(key)),
+                    e
+                );
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(match last_error {
+        Some(e) if keys.len() == 1 => e,
+        Some(e) => AiError::Config(format!(
+            "All {} API keys for {} are exhausted or invalid. Last error: {}",
+            keys.len(),
+            options.provider,
+            e
+        )),
+        None => AiError::Config(format!(
+            "All {} API keys for {} are exhausted or invalid",
+            keys.len(),
+            options.provider
+        )),
+    })
+}
+
+/// Prepare `file_path`, send it to the configured provider and parse the
+/// reply into title, description and keywords.  Identical requests are
+/// answered from the response cache unless `force_refresh` is set; usage of
+/// real requests is recorded in the ledger under `job_id`.  `cancel` stops
+/// the request while it is queued behind the rate limiter.
+pub fn generate_metadata(
+    file_path: &str,
+    options: &GenerateOptions,
+    backend: VideoBackend,
+    job_id: Option<&str>,
+    cancel: Option<&CancelToken>,
+) -> Result<MetadataResult, AiError> {
+    // Fail on missing settings before spending time on the image.
+    let keys = options.key_pool();
+    build_provider(options, keys.first().cloned())?;
+
+    let model = options
+        .model
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .or_else(|| options.provider.default_model())
+        .ok_or_else(|| {
+            AiError::Config("No local model selected. Please select a model from Settings.".into())
+        })?
+        .to_string();
+
+    let image = prepare_image(
+        file_path,
+        options.image_size.unwrap_or(DEFAULT_AI_IMAGE_SIZE),
+        backend,
+    )?;
+
+    let limits = options.limits();
+    let prompt = match options.prompt_template.as_deref() {
+        Some(template) => {
+            let context = TemplateContext::for_file(file_path, &limits);
+            template::render(template, &context).map_err(|e| AiError::Config(e.to_string()))?
+        }
+        None => options.prompt.clone(),
+    };
+    if prompt.trim().is_empty() {
+        return Err(AiError::Config("The prompt is empty".to_string()));
+    }
+
+    let request = AiRequest {
+        model,
+        prompt,
+        image,
+        max_tokens: options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        temperature: options.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+        json_schema: options
+            .structured_output
+            .unwrap_or_else(|| options.provider.supports_json_schema())
+            .then(parser::metadata_schema),
+    };
+
+    let base_url = options.base_url.as_deref().map(normalize_base_url);
+    let cache_key = cache::compute_cache_key(options.provider, base_url.as_deref(), &request);
+    if !options.force_refresh {
+        // An entry that no longer parses is treated as a miss.
+        let hit = cache::get_cached_response(&cache_key).and_then(|response| {
+            parser::parse_metadata_response(&response.text)
+                .ok()
+                .map(|metadata| (response, metadata))
+        });
+        if let Some((response, metadata)) = hit {
+            let warnings = parser::validate_metadata(&metadata, &limits);
+            return Ok(MetadataResult {
+                metadata,
+                provider: options.provider,
+                model: response.model,
+                warnings,
+                usage: None,
+                cost_usd: Some(0.0),
+                cached: true,
+            });
+        }
+    }
+
+    let response = generate_with_pool(options, &keys, &request, cancel)?;
+
+    // Billed whether or not the reply parses, so record it first.
+    let mut record = UsageRecord::new(
+        options.provider,
+        &response.model,
+        1,
+        response.usage,
+        &usage::load_price_table(),
+    );
+    record.job_id = job_id.map(str::to_string);
+    record.file_path = Some(file_path.to_string());
+    usage::record_usage(&record);
+
+    let metadata = parser::parse_metadata_response(&response.text)?;
+    // Only replies that parse are worth replaying.
+    cache::save_response_to_cache(&cache_key, &response);
+    let warnings = parser::validate_metadata(&metadata, &limits);
+    Ok(MetadataResult {
+        metadata,
+        provider: options.provider,
+        model: response.model,
+        warnings,
+        usage: response.usage,
+        cost_usd: record.cost_usd,
+        cached: false,
+    })
+}
@@ -0,0 +1,476 @@
+// Ollama's native API, for fully offline runs.  Requests go to `/api/chat`,
+// falling back to `/api/generate` on servers that predate it, and are
+// streamed: a CPU-only model can take minutes over a full reply, and
+// reading it chunk by chunk keeps every read inside the client timeout.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader};
+
+use super::usage::TokenUsage;
+use super::{
+    error_from_response, http_client, normalize_base_url, AiError, AiProvider, AiRequest,
+    AiResponse, ProviderKind,
+};
+use crate::services::secrets;
+
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+pub struct OllamaProvider {
+    /// Only needed behind an authenticating reverse proxy
+    api_key: Option<String>,
+    base_url: String,
+    keep_alive: Option<Value>,
+    client: reqwest::blocking::Client,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Chat,
+    Generate,
+}
+
+impl Endpoint {
+    fn path(&self) -> &'static str {
+        match self {
+            Endpoint::Chat => "/api/chat",
+            Endpoint::Generate => "/api/generate",
+        }
+    }
+}
+
+/// One line of a streamed reply.  `/api/chat` puts the text in `message`,
+/// `/api/generate` in `response`; the final line carries the counts.
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    message: Option<ChunkMessage>,
+    #[serde(default)]
+    response: Option<String>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+    /// Set when the server fails mid-stream (out of memory, model crash)
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkMessage {
+    #[serde(default)]
+    content: String,
+}
+
+/// An installed model, as listed by `/api/tags`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaModel {
+    pub name: String,
+    /// Size on disk in bytes
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+    #[serde(default)]
+    pub details: Option<OllamaModelDetails>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaModelDetails {
+    #[serde(default)]
+    pub family: Option<String>,
+    /// Vision models list a projector family here, e.g. `clip`
+    #[serde(default)]
+    pub families: Option<Vec<String>>,
+    #[serde(default)]
+    pub parameter_size: Option<String>,
+    #[serde(default)]
+    pub quantization_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
+}
+
+/// Ollama reads a bare number as seconds but a string as a Go duration, in
+/// which `-1` is invalid, so numeric settings are sent as numbers.
+fn keep_alive_value(value: &str) -> Option<Value> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    Some(match value.parse::<i64>() {
+        Ok(seconds) => json!(seconds),
+        Err(_) => json!(value),
+    })
+}
+
+/// A plain 404 without a JSON error means the route itself is missing.
+fn is_missing_endpoint(error: &AiError) -> bool {
+    matches!(error, AiError::Http { status: 404, body, .. } if !body.contains("\"error\""))
+}
+
+/// Servers older than 0.5 only accept `"format": "json"` and fail to
+/// decode a schema object.
+fn is_format_rejection(error: &AiError) -> bool {
+    let AiError::Http {
+        status: 400, body, ..
+    } = error
+    else {
+        return false;
+    };
+    let lower = body.to_lowercase();
+    lower.contains(".format") || lower.contains("invalid format")
+}
+
+impl OllamaProvider {
+    /// `base_url` is the server root without `/api`, defaulting to
+    /// `http://localhost:11434`.
+    pub fn new(
+        api_key: Option<String>,
+        base_url: Option<String>,
+        keep_alive: Option<&str>,
+    ) -> Result<Self, AiError> {
+        Ok(Self {
+            api_key,
+            base_url: base_url.unwrap_or_else(|| DEFAULT_OLLAMA_BASE_URL.to_string()),
+            keep_alive: keep_alive.and_then(keep_alive_value),
+            client: http_client()?,
+        })
+    }
+
+    fn payload(&self, endpoint: Endpoint, request: &AiRequest, format: Option<&Value>) -> Value {
+        let image = request.image.base64();
+        let mut payload = match endpoint {
+            Endpoint::Chat => json!({
+                "model": request.model,
+                "messages": [{
+                    "role": "user",
+                    "content": request.prompt,
+                    "images": [image],
+                }],
+            }),
+            Endpoint::Generate => json!({
+                "model": request.model,
+                "prompt": request.prompt,
+                "images": [image],
+            }),
+        };
+        payload["stream"] = json!(true);
+        payload["options"] = json!({
+            "temperature": request.temperature,
+            "num_predict": request.max_tokens,
+        });
+        if let Some(format) = format {
+            payload["format"] = format.clone();
+        }
+        if let Some(ref keep_alive) = self.keep_alive {
+            payload["keep_alive"] = keep_alive.clone();
+        }
+        payload
+    }
+
+    fn send(
+        &self,
+        endpoint: Endpoint,
+        payload: &Value,
+        model: &str,
+    ) -> Result<AiResponse, AiError> {
+        let url = format!("{}{}", self.base_url, endpoint.path());
+        let mut builder = self.client.post(&url).json(payload);
+        if let Some(ref key) = self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let response = builder.send()?;
+        if !response.status().is_success() {
+            let error = error_from_response(response, model);
+            return Err(match error {
+                AiError::Http {
+                    status: 404, body, ..
+                } if body.contains("not found") && body.contains(model) => {
+                    AiError::Config(format!(
+                        "Model {} is not installed in Ollama. Run `ollama pull {}` first.",
+                        model, model
+                    ))
+                }
+                other => other,
+            });
+        }
+
+        let mut text = String::new();
+        let mut model_name = None;
+        let mut usage = None;
+        for line in BufReader::new(response).lines() {
+            let line = line.map_err(|e| AiError::Network(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: StreamChunk = serde_json::from_str(&line)
+                .map_err(|e| AiError::InvalidResponse(format!("{}: {}", e, line)))?;
+            if let Some(error) = chunk.error {
+                return Err(AiError::InvalidResponse(error));
+            }
+            if let Some(message) = chunk.message {
+                text.push_str(&message.content);
+            }
+            if let Some(response) = chunk.response {
+                text.push_str(&response);
+            }
+            if chunk.done {
+                model_name = chunk.model;
+                usage = Some(TokenUsage {
+                    input_tokens: chunk.prompt_eval_count,
+                    output_tokens: chunk.eval_count,
+                    reported_cost: None,
+                });
+                break;
+            }
+        }
+
+        if usage.is_none() {
+            return Err(AiError::Network(
+                "Ollama closed the stream before the reply finished".to_string(),
+            ));
+        }
+        if text.trim().is_empty() {
+            return Err(AiError::InvalidResponse(
+                "The model returned an empty response".to_string(),
+            ));
+        }
+
+        Ok(AiResponse {
+            text,
+            model: model_name.unwrap_or_else(|| model.to_string()),
+            usage,
+        })
+    }
+
+    /// Models installed on the server
+    pub fn list_models(&self) -> Result<Vec<OllamaModel>, AiError> {
+        let mut builder = self.client.get(format!("{}/api/tags", self.base_url));
+        if let Some(ref key) = self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        let response = builder.send()?;
+        if !response.status().is_success() {
+            return Err(error_from_response(response, ""));
+        }
+        let tags: TagsResponse = response
+            .json()
+            .map_err(|e| AiError::InvalidResponse(e.to_string()))?;
+        Ok(tags.models)
+    }
+}
+
+/// Models installed on the server at `base_url` (the local default when
+/// unset), using a stored key if the server sits behind a proxy
+pub fn installed_models(base_url: Option<&str>) -> Result<Vec<OllamaModel>, AiError> {
+    let base_url = base_url.map(normalize_base_url).filter(|u| !u.is_empty());
+    let api_key = secrets::api_keys(ProviderKind::Ollama)
+        .ok()
+        .and_then(|keys| keys.into_iter().next());
+    OllamaProvider::new(api_key, base_url, None)?.list_models()
+}
+
+impl AiProvider for OllamaProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Ollama
+    }
+
+    fn generate(&self, request: &AiRequest) -> Result<AiResponse, AiError> {
+        let mut endpoint = Endpoint::Chat;
+        let mut format = request.json_schema.clone();
+        // Each fallback applies at most once, so this ends after three tries.
+        loop {
+            let payload = self.payload(endpoint, request, format.as_ref());
+            match self.send(endpoint, &payload, &request.model) {
+                Err(e) if endpoint == Endpoint::Chat && is_missing_endpoint(&e) => {
+                    endpoint = Endpoint::Generate;
+                }
+                Err(e)
+                    if format.as_ref().is_some_and(Value::is_object) && is_format_rejection(&e) =>
+                {
+                    format = Some(json!("json"));
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai::stub_server::{test_request, StubResponse, StubServer};
+
+    fn provider(server: &StubServer) -> OllamaProvider {
+        OllamaProvider::new(None, Some(server.url.clone()), Some("10m")).unwrap()
+    }
+
+    #[test]
+    fn keep_alive_numbers_are_seconds() {
+        assert_eq!(keep_alive_value("-1"), Some(json!(-1)));
+        assert_eq!(keep_alive_value(" 300 "), Some(json!(300)));
+        assert_eq!(keep_alive_value("0"), Some(json!(0)));
+        assert_eq!(keep_alive_value("10m"), Some(json!("10m")));
+        assert_eq!(keep_alive_value("1h30m"), Some(json!("1h30m")));
+        assert_eq!(keep_alive_value(""), None);
+        assert_eq!(keep_alive_value("  "), None);
+    }
+
+    #[test]
+    fn streams_chat_reply_with_final_counts() {
+        let server = StubServer::start(vec![StubResponse::ndjson(&[
+            r#"{"model":"llava:7b","message":{"role":"assistant","content":"{\"title\":"},"done":false}"#,
+            "",
+            r#"{"model":"llava:7b","message":{"role":"assistant","content":"\"Sunset\"}"},"done":false}"#,
+            r#"{"model":"llava:7b","message":{"role":"assistant","content":""},"done":true,"prompt_eval_count":640,"eval_count":42}"#,
+        ])]);
+        let response = provider(&server).generate(&test_request("llava")).unwrap();
+
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+        assert_eq!(response.model, "llava:7b");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.input_tokens, 640);
+        assert_eq!(usage.output_tokens, 42);
+        assert_eq!(usage.reported_cost, None);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/api/chat");
+        let payload = requests[0].json();
+        assert_eq!(payload["stream"], true);
+        assert_eq!(payload["keep_alive"], "10m");
+        assert_eq!(payload["format"]["type"], "object");
+        assert_eq!(payload["options"]["num_predict"], 200);
+        assert_eq!(
+            payload["messages"][0]["images"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn falls_back_to_generate_when_chat_is_missing() {
+        let server = StubServer::start(vec![
+            StubResponse::text(404, "404 page not found"),
+            StubResponse::ndjson(&[
+                r#"{"model":"llava","response":"{\"title\":\"Sunset\"}","done":false}"#,
+                r#"{"model":"llava","response":"","done":true,"prompt_eval_count":600,"eval_count":12}"#,
+            ]),
+        ]);
+        let response = provider(&server).generate(&test_request("llava")).unwrap();
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+        assert_eq!(response.usage.unwrap().output_tokens, 12);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/api/chat");
+        assert_eq!(requests[1].path, "/api/generate");
+        let payload = requests[1].json();
+        assert_eq!(payload["prompt"], "Describe this image");
+        assert!(payload.get("messages").is_none());
+    }
+
+    #[test]
+    fn retries_with_plain_json_format_when_schema_rejected() {
+        let server = StubServer::start(vec![
+            StubResponse::json(
+                400,
+                r#"{"error":"json: cannot unmarshal object into Go struct field ChatRequest.format of type string"}"#,
+            ),
+            StubResponse::ndjson(&[
+                r#"{"model":"llava","message":{"content":"{\"title\":\"Sunset\"}"},"done":true,"prompt_eval_count":1,"eval_count":1}"#,
+            ]),
+        ]);
+        provider(&server).generate(&test_request("llava")).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].json()["format"].is_object());
+        assert_eq!(requests[1].json()["format"], "json");
+        assert_eq!(requests[1].path, "/api/chat");
+    }
+
+    #[test]
+    fn error_line_mid_stream_fails_the_request() {
+        let server = StubServer::start(vec![StubResponse::ndjson(&[
+            r#"{"model":"llava","message":{"content":"{\"ti"},"done":false}"#,
+            r#"{"error":"model runner has unexpectedly stopped"}"#,
+        ])]);
+        match provider(&server).generate(&test_request("llava")) {
+            Err(AiError::InvalidResponse(msg)) => {
+                assert_eq!(msg, "model runner has unexpectedly stopped")
+            }
+            other => panic!("expected a stream error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stream_closed_before_done_is_a_network_error() {
+        let server = StubServer::start(vec![StubResponse::ndjson(&[
+            r#"{"model":"llava","message":{"content":"{\"title\":\"Sun"},"done":false}"#,
+        ])]);
+        match provider(&server).generate(&test_request("llava")) {
+            Err(AiError::Network(msg)) => assert!(msg.contains("before the reply finished")),
+            other => panic!("expected a truncated stream, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_model_is_a_config_error() {
+        let server = StubServer::start(vec![StubResponse::json(
+            404,
+            r#"{"error":"model \"llava\" not found, try pulling it first"}"#,
+        )]);
+        match provider(&server).generate(&test_request("llava")) {
+            Err(AiError::Config(msg)) => assert!(msg.contains("ollama pull llava")),
+            other => panic!("expected a config error, got {:?}", other),
+        }
+        // A JSON 404 is not a missing route, so /api/generate is not tried.
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn lists_installed_models() {
+        let server = StubServer::start(vec![StubResponse::json(
+            200,
+            r#"{"models":[
+                {
+                    "name": "llava:7b",
+                    "model": "llava:7b",
+                    "modified_at": "2025-01-10T09:30:00Z",
+                    "size": 4733363377,
+                    "details": {
+                        "family": "llama",
+                        "families": ["llama", "clip"],
+                        "parameter_size": "7B",
+                        "quantization_level": "Q4_0"
+                    }
+                },
+                { "name": "moondream:latest" }
+            ]}"#,
+        )]);
+        let models = provider(&server).list_models().unwrap();
+
+        assert_eq!(server.requests()[0].method, "GET");
+        assert_eq!(server.requests()[0].path, "/api/tags");
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].name, "llava:7b");
+        assert_eq!(models[0].size, 4_733_363_377);
+        let details = models[0].details.as_ref().unwrap();
+        assert_eq!(
+            details.families.as_deref(),
+            Some(&["llama".to_string(), "clip".to_string()][..])
+        );
+        assert_eq!(details.parameter_size.as_deref(), Some("7B"));
+        assert_eq!(models[1].name, "moondream:latest");
+        assert_eq!(models[1].size, 0);
+        assert!(models[1].details.is_none());
+    }
+}
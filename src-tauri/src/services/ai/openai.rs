@@ -0,0 +1,320 @@
+// OpenAI-compatible `chat/completions` provider, shared by OpenAI,
+// OpenRouter and local servers such as LM Studio
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::usage::TokenUsage;
+use super::{
+    error_from_response, http_client, is_schema_rejection, AiError, AiProvider, AiRequest,
+    AiResponse, ProviderKind,
+};
+
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+pub const DEFAULT_OPENROUTER_MODEL: &str = "google/gemini-2.0-flash-001";
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+pub struct OpenAiCompatibleProvider {
+    kind: ProviderKind,
+    api_key: Option<String>,
+    base_url: String,
+    client: reqwest::blocking::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    #[serde(default)]
+    choices: Vec<Choice>,
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    /// OpenRouter's billed amount in USD (credits)
+    #[serde(default)]
+    cost: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    /// Usually a string, but some servers return an array of content parts
+    content: Option<Value>,
+}
+
+impl OpenAiCompatibleProvider {
+    /// `base_url` must include the API version (`http://localhost:1234/v1`);
+    /// it is required for `ProviderKind::Local`.
+    pub fn new(
+        kind: ProviderKind,
+        api_key: Option<String>,
+        base_url: Option<String>,
+    ) -> Result<Self, AiError> {
+        let base_url = match (base_url, kind) {
+            (Some(url), _) => url,
+            (None, ProviderKind::Openrouter) => OPENROUTER_BASE_URL.to_string(),
+            (None, ProviderKind::Openai) => OPENAI_BASE_URL.to_string(),
+            (None, _) => {
+                return Err(AiError::Config(format!(
+                    "No base URL configured for {}",
+                    kind
+                )))
+            }
+        };
+        Ok(Self {
+            kind,
+            api_key,
+            base_url,
+            client: http_client()?,
+        })
+    }
+
+    fn send(
+        &self,
+        url: &str,
+        payload: &Value,
+        model: &str,
+    ) -> Result<ChatCompletionResponse, AiError> {
+        let mut builder = self.client.post(url).json(payload);
+        if let Some(ref key) = self.api_key {
+            builder = builder.bearer_auth(key);
+        }
+        if self.kind == ProviderKind::Openrouter {
+            // Optional attribution headers used for OpenRouter's app rankings
+            builder = builder
+                .header("HTTP-Referer", "https://descify.app")
+                .header("X-Title", "Descify");
+        }
+
+        let response = builder.send()?;
+        if !response.status().is_success() {
+            return Err(error_from_response(response, model));
+        }
+
+        response
+            .json()
+            .map_err(|e| AiError::InvalidResponse(e.to_string()))
+    }
+}
+
+/// Joins string content or the text parts of array content.
+fn message_text(content: Value) -> String {
+    match content {
+        Value::String(s) => s,
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
+}
+
+impl AiProvider for OpenAiCompatibleProvider {
+    fn kind(&self) -> ProviderKind {
+        self.kind
+    }
+
+    fn generate(&self, request: &AiRequest) -> Result<AiResponse, AiError> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        let mut payload = json!({
+            "model": request.model,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": request.prompt },
+                    { "type": "image_url", "image_url": { "url": request.image.data_url() } }
+                ]
+            }],
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature,
+        });
+
+        if self.kind == ProviderKind::Openrouter {
+            // Ask OpenRouter to include the billed cost in `usage`.
+            payload["usage"] = json!({ "include": true });
+        }
+        if let Some(ref schema) = request.json_schema {
+            payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "stock_metadata",
+                    "strict": true,
+                    "schema": schema,
+                }
+            });
+        }
+
+        let body = match self.send(&url, &payload, &request.model) {
+            // Models without structured output reject `response_format`;
+            // the parser copes with plain text.
+            Err(e) if request.json_schema.is_some() && is_schema_rejection(&e) => {
+                if let Some(map) = payload.as_object_mut() {
+                    map.remove("response_format");
+                }
+                self.send(&url, &payload, &request.model)?
+            }
+            result => result?,
+        };
+
+        let usage = body.usage.map(|u| TokenUsage {
+            input_tokens: u.prompt_tokens,
+            output_tokens: u.completion_tokens,
+            reported_cost: u.cost,
+        });
+
+        let text = body
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message)
+            .and_then(|m| m.content)
+            .map(message_text)
+            .unwrap_or_default();
+
+        if text.trim().is_empty() {
+            return Err(AiError::InvalidResponse(
+                "The model returned an empty response".to_string(),
+            ));
+        }
+
+        Ok(AiResponse {
+            text,
+            model: body.model.unwrap_or_else(|| request.model.clone()),
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ai::stub_server::{test_request, StubResponse, StubServer};
+
+    const REPLY: &str = r#"{
+        "model": "gpt-4o-mini-2024-07-18",
+        "choices": [{ "message": { "role": "assistant", "content": "{\"title\":\"Sunset\"}" } }],
+        "usage": { "prompt_tokens": 850, "completion_tokens": 60 }
+    }"#;
+
+    fn provider(kind: ProviderKind, server: &StubServer) -> OpenAiCompatibleProvider {
+        OpenAiCompatibleProvider::new(kind, Some("sk-test".into()), Some(server.url.clone()))
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_reply_and_usage() {
+        let server = StubServer::start(vec![StubResponse::json(200, REPLY)]);
+        let response = provider(ProviderKind::Openai, &server)
+            .generate(&test_request("gpt-4o-mini"))
+            .unwrap();
+
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+        assert_eq!(response.model, "gpt-4o-mini-2024-07-18");
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.input_tokens, 850);
+        assert_eq!(usage.output_tokens, 60);
+        assert_eq!(usage.reported_cost, None);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/chat/completions");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer sk-test"));
+        let payload = requests[0].json();
+        assert_eq!(payload["response_format"]["type"], "json_schema");
+        assert!(payload.get("usage").is_none());
+    }
+
+    #[test]
+    fn openrouter_asks_for_and_reports_cost() {
+        let server = StubServer::start(vec![StubResponse::json(
+            200,
+            r#"{
+                "choices": [{ "message": { "content": [{ "type": "text", "text": "{\"title\":" }, { "type": "text", "text": "\"Sunset\"}" }] } }],
+                "usage": { "prompt_tokens": 1000, "completion_tokens": 50, "cost": 0.00042 }
+            }"#,
+        )]);
+        let response = provider(ProviderKind::Openrouter, &server)
+            .generate(&test_request("google/gemini-2.0-flash-001"))
+            .unwrap();
+
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+        assert_eq!(response.model, "google/gemini-2.0-flash-001");
+        assert_eq!(response.usage.unwrap().reported_cost, Some(0.00042));
+
+        let request = &server.requests()[0];
+        assert_eq!(request.json()["usage"]["include"], true);
+        assert_eq!(request.header("x-title"), Some("Descify"));
+    }
+
+    #[test]
+    fn retries_without_response_format_when_rejected() {
+        let server = StubServer::start(vec![
+            StubResponse::json(
+                400,
+                r#"{"error":{"message":"response_format json_schema is not supported for this model"}}"#,
+            ),
+            StubResponse::json(200, REPLY),
+        ]);
+        let response = provider(ProviderKind::Local, &server)
+            .generate(&test_request("llava"))
+            .unwrap();
+        assert_eq!(response.text, r#"{"title":"Sunset"}"#);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].json().get("response_format").is_some());
+        let retry = requests[1].json();
+        assert!(retry.get("response_format").is_none());
+        assert_eq!(retry["model"], "llava");
+    }
+
+    #[test]
+    fn maps_error_statuses_to_http_errors() {
+        let server = StubServer::start(vec![
+            StubResponse::json(401, r#"{"error":{"message":"Incorrect API key"}}"#),
+            StubResponse::text(502, "bad gateway"),
+        ]);
+        let provider = provider(ProviderKind::Openai, &server);
+
+        match provider.generate(&test_request("gpt-4o-mini")) {
+            Err(AiError::Http { status, body, .. }) => {
+                assert_eq!(status, 401);
+                assert!(body.contains("Incorrect API key"));
+            }
+            other => panic!("expected a 401, got {:?}", other),
+        }
+        match provider.generate(&test_request("gpt-4o-mini")) {
+            Err(AiError::Http { status, body, .. }) => {
+                assert_eq!(status, 502);
+                assert_eq!(body, "bad gateway");
+            }
+            other => panic!("expected a 502, got {:?}", other),
+        }
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn image_rejection_is_unsupported_model() {
+        let server = StubServer::start(vec![StubResponse::json(
+            400,
+            r#"{"error":{"message":"This model does not support image input"}}"#,
+        )]);
+        match provider(ProviderKind::Openai, &server).generate(&test_request("gpt-3.5-turbo")) {
+            Err(AiError::UnsupportedModel(model)) => assert_eq!(model, "gpt-3.5-turbo"),
+            other => panic!("expected UnsupportedModel, got {:?}", other),
+        }
+    }
+}
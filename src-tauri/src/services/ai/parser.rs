@@ -0,0 +1,431 @@
+// Turns the model's reply into title/description/keywords.  Replies arrive
+// as bare JSON, JSON inside a ```json fence, JSON surrounded by chatter, or
+// several objects in a row, and small models like trailing commas, so the
+// parser is lenient about the wrapping and strict about the content.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::AiError;
+use crate::models::jobs::MetadataLimits;
+
+pub(crate) const DEFAULT_KEYWORD_LIMIT: usize = 80;
+const MIN_TITLE_CHARS: usize = 5;
+const MIN_DESCRIPTION_CHARS: usize = 10;
+/// How much of the reply to quote in error messages
+const EXCERPT_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedMetadata {
+    pub title: String,
+    pub description: String,
+    pub keywords: Vec<String>,
+}
+
+/// JSON schema of the reply, for providers with a native structured output
+/// mode.  Keywords are an array here; free-form replies may still use a
+/// comma-separated string.
+pub fn metadata_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "title": { "type": "string" },
+            "description": { "type": "string" },
+            "keywords": { "type": "array", "items": { "type": "string" } }
+        },
+        "required": ["title", "description", "keywords"],
+        "additionalProperties": false
+    })
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    let mut out: String = text.chars().take(EXCERPT_CHARS).collect();
+    if text.chars().count() > EXCERPT_CHARS {
+        out.push('…');
+    }
+    out
+}
+
+/// Bodies of ``` fenced blocks, in order.  An unterminated fence (the reply
+/// was cut off) runs to the end of the text.
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        // Skip the info string (`json`, `JSON`, ...) up to the newline.
+        let body_start = after.find('\n').map(|i| i + 1).unwrap_or(after.len());
+        let body = &after[body_start..];
+        match body.find("```") {
+            Some(end) => {
+                blocks.push(&body[..end]);
+                rest = &body[end + 3..];
+            }
+            None => {
+                blocks.push(body);
+                break;
+            }
+        }
+    }
+    blocks
+}
+
+/// Top-level `{...}` spans, matched by brace depth while skipping over
+/// string literals, so braces inside titles don't confuse it.
+fn json_objects(text: &str) -> Vec<&str> {
+    let mut objects = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' if depth > 0 => in_string = true,
+            '{' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    objects.push(&text[start..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    objects
+}
+
+/// Drop commas that directly precede `}` or `]`, outside string literals.
+fn strip_trailing_commas(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let chars: Vec<char> = json.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            out.push(c);
+            continue;
+        }
+        if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn parse_object(candidate: &str) -> Result<Map<String, Value>, serde_json::Error> {
+    let value = match serde_json::from_str::<Value>(candidate) {
+        Ok(v) => v,
+        Err(e) => {
+            // Only fall back to the repaired text if it actually helps, so
+            // the error points at the model's original syntax.
+            let repaired = strip_trailing_commas(candidate);
+            serde_json::from_str::<Value>(&repaired).map_err(|_| e)?
+        }
+    };
+    match value {
+        Value::Object(map) => Ok(map),
+        // Can't happen for a `{...}` span, but keep the types honest.
+        other => Err(serde::de::Error::custom(format!(
+            "expected an object, got {}",
+            type_name(&other)
+        ))),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Field lookup that tolerates `Title`, `TITLE` and `tags` for keywords.
+fn field<'a>(object: &'a Map<String, Value>, name: &str) -> Option<&'a Value> {
+    let aliases: &[&str] = match name {
+        "keywords" => &["keywords", "tags"],
+        _ => &[name],
+    };
+    object
+        .iter()
+        .find(|(k, _)| aliases.iter().any(|a| k.trim().eq_ignore_ascii_case(a)))
+        .map(|(_, v)| v)
+}
+
+fn has_metadata_fields(object: &Map<String, Value>) -> bool {
+    ["title", "description", "keywords"]
+        .iter()
+        .any(|f| field(object, f).is_some())
+}
+
+/// Models sometimes wrap the answer: `{"metadata": {...}}`.
+fn unwrap_nested(object: Map<String, Value>) -> Map<String, Value> {
+    if has_metadata_fields(&object) || object.len() != 1 {
+        return object;
+    }
+    match object.into_iter().next() {
+        Some((_, Value::Object(inner))) if has_metadata_fields(&inner) => inner,
+        Some((k, v)) => Map::from_iter([(k, v)]),
+        None => Map::new(),
+    }
+}
+
+/// Every object that carries metadata fields, fenced blocks first.  When a
+/// reply splits the fields over several objects they are merged, earlier
+/// objects winning.
+fn collect_metadata_object(text: &str) -> Result<Map<String, Value>, AiError> {
+    let mut sources = fenced_blocks(text);
+    sources.push(text);
+
+    let mut merged: Option<Map<String, Value>> = None;
+    let mut first_error: Option<(usize, serde_json::Error)> = None;
+    let mut candidates = 0;
+
+    for source in sources {
+        for candidate in json_objects(source) {
+            candidates += 1;
+            let object = match parse_object(candidate) {
+                Ok(o) => unwrap_nested(o),
+                Err(e) => {
+                    first_error.get_or_insert((candidates, e));
+                    continue;
+                }
+            };
+            if !has_metadata_fields(&object) {
+                continue;
+            }
+            let target = merged.get_or_insert_with(Map::new);
+            for (k, v) in object {
+                if field(target, &k.to_lowercase()).is_none() {
+                    target.insert(k, v);
+                }
+            }
+        }
+        // Objects inside a fence are the answer; the surrounding text only
+        // repeats them.
+        if merged.is_some() {
+            break;
+        }
+    }
+
+    if let Some(object) = merged {
+        return Ok(object);
+    }
+    if let Some((index, e)) = first_error {
+        return Err(AiError::Parse(format!(
+            "Invalid JSON in object {}: {}",
+            index, e
+        )));
+    }
+    if candidates > 0 {
+        return Err(AiError::Parse(
+            "The JSON in the response has no title, description or keywords".to_string(),
+        ));
+    }
+    if text.trim_start().starts_with('<') {
+        return Err(AiError::Parse(
+            "AI returned HTML instead of JSON - check API key and rate limits".to_string(),
+        ));
+    }
+    if text.contains('{') {
+        return Err(AiError::Parse(
+            "The JSON in the response is incomplete; it may have been cut off by the token limit"
+                .to_string(),
+        ));
+    }
+    Err(AiError::Parse(format!(
+        "No JSON object found in the response: \"{}\"",
+        excerpt(text)
+    )))
+}
+
+fn text_field(
+    object: &Map<String, Value>,
+    name: &str,
+    min_chars: usize,
+) -> Result<String, AiError> {
+    let value = field(object, name)
+        .ok_or_else(|| AiError::Parse(format!("Field \"{}\" is missing", name)))?;
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        other => {
+            return Err(AiError::Parse(format!(
+                "Field \"{}\" must be a string, got {}",
+                name,
+                type_name(other)
+            )))
+        }
+    };
+    let len = text.chars().count();
+    if len < min_chars {
+        return Err(AiError::Parse(format!(
+            "AI returned invalid {} (too short: {} characters, minimum {})",
+            name, len, min_chars
+        )));
+    }
+    Ok(text)
+}
+
+/// Keywords as an array, or a single string separated by commas,
+/// semicolons or newlines.  Deduplicated case-insensitively.
+fn keywords_field(object: &Map<String, Value>) -> Result<Vec<String>, AiError> {
+    let raw: Vec<String> = match field(object, "keywords") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(s)) => s.split([',', ';', '\n']).map(str::to_string).collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                Value::String(s) => Ok(s.clone()),
+                Value::Number(n) => Ok(n.to_string()),
+                other => Err(AiError::Parse(format!(
+                    "Keyword {} must be a string, got {}",
+                    i + 1,
+                    type_name(other)
+                ))),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(other) => {
+            return Err(AiError::Parse(format!(
+                "Field \"keywords\" must be an array or a comma-separated string, got {}",
+                type_name(other)
+            )))
+        }
+    };
+
+    let mut keywords: Vec<String> = Vec::new();
+    for keyword in raw {
+        let keyword = keyword.trim().trim_matches(['"', '\'', '#']).trim();
+        if !keyword.is_empty()
+            && !keywords
+                .iter()
+                .any(|k| k.to_lowercase() == keyword.to_lowercase())
+        {
+            keywords.push(keyword.to_string());
+        }
+    }
+    Ok(keywords)
+}
+
+/// Parse the JSON object the prompt asks for and check it is usable.
+/// Keywords are kept in full; `validate_metadata` reports any over the limit.
+pub fn parse_metadata_response(text: &str) -> Result<GeneratedMetadata, AiError> {
+    let object = collect_metadata_object(text)?;
+
+    let title = text_field(&object, "title", MIN_TITLE_CHARS)?;
+    let description = text_field(&object, "description", MIN_DESCRIPTION_CHARS)?;
+    let keywords = keywords_field(&object)?;
+
+    Ok(GeneratedMetadata {
+        title,
+        description,
+        keywords,
+    })
+}
+
+/// Length and count checks against the configured limits.  The limits are
+/// guidance for the model, so callers treat these as warnings.
+pub fn validate_metadata(metadata: &GeneratedMetadata, limits: &MetadataLimits) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut check_length = |name: &str, text: &str, limit: usize| {
+        let len = text.chars().count();
+        if len > limit {
+            warnings.push(format!(
+                "{} is {} characters, {} over the {} character limit",
+                name,
+                len,
+                len - limit,
+                limit
+            ));
+        }
+    };
+    check_length("Title", &metadata.title, limits.title_limit);
+    check_length(
+        "Description",
+        &metadata.description,
+        limits.description_limit,
+    );
+
+    let count = metadata.keywords.len();
+    if count > limits.keyword_limit {
+        warnings.push(format!(
+            "{} keywords, {} over the limit of {}",
+            count,
+            count - limits.keyword_limit,
+            limits.keyword_limit
+        ));
+    }
+    if count == 0 {
+        warnings.push("No keywords were generated".to_string());
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(keyword_count: usize) -> String {
+        let keywords: Vec<String> = (0..keyword_count).map(|i| format!("tag{}", i)).collect();
+        json!({
+            "title": "Sunset over the harbour",
+            "description": "Fishing boats moored in a calm harbour at dusk",
+            "keywords": keywords,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn keeps_every_keyword_and_warns_over_the_limit() {
+        let metadata = parse_metadata_response(&reply(60)).unwrap();
+        assert_eq!(metadata.keywords.len(), 60);
+
+        let limits = MetadataLimits {
+            keyword_limit: 49,
+            ..MetadataLimits::default()
+        };
+        let warnings = validate_metadata(&metadata, &limits);
+        assert_eq!(warnings, vec!["60 keywords, 11 over the limit of 49"]);
+    }
+
+    #[test]
+    fn no_keyword_warning_within_the_limit() {
+        let metadata = parse_metadata_response(&reply(49)).unwrap();
+        let limits = MetadataLimits {
+            keyword_limit: 49,
+            ..MetadataLimits::default()
+        };
+        assert!(validate_metadata(&metadata, &limits).is_empty());
+    }
+}
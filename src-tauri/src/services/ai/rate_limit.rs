@@ -0,0 +1,645 @@
+// Per-provider, per-key request pacing.  Each key gets a token bucket plus
+// a concurrency cap; both grow while requests succeed and are halved when
+// the provider starts answering 429/503 (AIMD), so free keys settle just
+// under their quota and paid keys ramp up to use theirs.
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::key_pool;
+use super::{AiError, ProviderKind};
+use crate::services::process::CancelToken;
+
+lazy_static! {
+    pub static ref RATE_LIMITER: RateLimiter = RateLimiter::new();
+}
+
+const MAX_ATTEMPTS: u32 = 5;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_CAP: Duration = Duration::from_secs(60);
+/// Upper bound on an honoured `Retry-After`
+const RETRY_AFTER_CAP: Duration = Duration::from_secs(300);
+/// Consecutive successes before the rate and concurrency step back up
+const RAMP_UP_STREAK: u32 = 5;
+/// How often waits check for cancellation; `CancelToken` has no wakeup.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Starting pace for a provider, before any adaptation
+struct Limits {
+    requests_per_sec: f64,
+    min_requests_per_sec: f64,
+    max_requests_per_sec: f64,
+    burst: f64,
+    concurrency: usize,
+    max_concurrency: usize,
+}
+
+impl ProviderKind {
+    fn rate_limits(&self) -> Limits {
+        match self {
+            // Free Gemini keys allow ~15 RPM on the flash models; start a
+            // little above that and let 429s pull it down.
+            ProviderKind::Google => Limits {
+                requests_per_sec: 0.5,
+                min_requests_per_sec: 0.1,
+                max_requests_per_sec: 20.0,
+                burst: 2.0,
+                concurrency: 2,
+                max_concurrency: 16,
+            },
+            ProviderKind::Openai | ProviderKind::Openrouter => Limits {
+                requests_per_sec: 2.0,
+                min_requests_per_sec: 0.1,
+                max_requests_per_sec: 50.0,
+                burst: 4.0,
+                concurrency: 4,
+                max_concurrency: 16,
+            },
+            // A local server is bound by its own GPU, not by a quota.
+            ProviderKind::Local | ProviderKind::Ollama => Limits {
+                requests_per_sec: 100.0,
+                min_requests_per_sec: 100.0,
+                max_requests_per_sec: 100.0,
+                burst: 1.0,
+                concurrency: 1,
+                max_concurrency: 2,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BucketKey {
+    provider: ProviderKind,
+    key_id: String,
+}
+
+/// Short, non-reversible id for an API key, safe to log and show in the UI.
+pub fn key_id(api_key: Option<&str>) -> String {
+    match api_key.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(key) => blake3::hash(key.as_bytes()).to_hex()[..12].to_string(),
+        None => "default".to_string(),
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    burst: f64,
+    refill_per_sec: f64,
+    min_refill_per_sec: f64,
+    max_refill_per_sec: f64,
+    last_refill: Instant,
+    concurrency_limit: usize,
+    max_concurrency: usize,
+    in_flight: usize,
+    blocked_until: Option<Instant>,
+    success_streak: u32,
+    total_requests: u64,
+    throttled_responses: u64,
+    last_error: Option<String>,
+}
+
+impl Bucket {
+    fn new(provider: ProviderKind, now: Instant) -> Self {
+        let limits = provider.rate_limits();
+        Self {
+            tokens: limits.burst,
+            burst: limits.burst,
+            refill_per_sec: limits.requests_per_sec,
+            min_refill_per_sec: limits.min_requests_per_sec,
+            max_refill_per_sec: limits.max_requests_per_sec,
+            last_refill: now,
+            concurrency_limit: limits.concurrency,
+            max_concurrency: limits.max_concurrency,
+            in_flight: 0,
+            blocked_until: None,
+            success_streak: 0,
+            total_requests: 0,
+            throttled_responses: 0,
+            last_error: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// How long until a request may start, or `None` if it can start now.
+    fn wait_time(&mut self, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        if let Some(until) = self.blocked_until {
+            if until > now {
+                return Some(until - now);
+            }
+            self.blocked_until = None;
+        }
+        if self.in_flight >= self.concurrency_limit {
+            // Woken when a permit drops; the timeout only guards against missed wakeups.
+            return Some(Duration::from_secs(1));
+        }
+        if self.tokens < 1.0 {
+            return Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_sec,
+            ));
+        }
+        None
+    }
+
+    fn on_success(&mut self) {
+        self.success_streak += 1;
+        if self.success_streak >= RAMP_UP_STREAK {
+            self.success_streak = 0;
+            self.concurrency_limit = (self.concurrency_limit + 1).min(self.max_concurrency);
+            self.refill_per_sec = (self.refill_per_sec * 1.25).min(self.max_refill_per_sec);
+        }
+    }
+
+    fn on_throttled(&mut self, now: Instant, cooldown: Duration) {
+        self.throttled_responses += 1;
+        self.success_streak = 0;
+        self.concurrency_limit = (self.concurrency_limit / 2).max(1);
+        self.refill_per_sec = (self.refill_per_sec / 2.0).max(self.min_refill_per_sec);
+        self.tokens = 0.0;
+        let until = now + cooldown;
+        self.blocked_until = Some(self.blocked_until.map_or(until, |b| b.max(until)));
+    }
+}
+
+/// Snapshot of one bucket for `get_rate_limit_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitStatus {
+    pub provider: ProviderKind,
+    pub key_id: String,
+    pub requests_per_minute: f64,
+    pub tokens_available: f64,
+    pub burst: f64,
+    pub concurrency_limit: usize,
+    pub in_flight: usize,
+    /// Remaining cooldown after a 429/503, in milliseconds
+    pub blocked_for_ms: u64,
+    pub total_requests: u64,
+    pub throttled_responses: u64,
+    pub last_error: Option<String>,
+}
+
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    changed: Condvar,
+}
+
+/// Holds one concurrency slot; released on drop.
+struct Permit<'a> {
+    limiter: &'a RateLimiter,
+    key: BucketKey,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(bucket) = self.limiter.lock().get_mut(&self.key) {
+            bucket.in_flight = bucket.in_flight.saturating_sub(1);
+        }
+        self.limiter.changed.notify_all();
+    }
+}
+
+/// Statuses worth retrying: throttling and transient server errors.  A key
+/// that is out of quota or rejected won't recover by waiting, so those go
+/// straight back to the key pool to rotate.
+fn is_retryable(error: &AiError) -> bool {
+    if key_pool::classify_error(error).is_some() {
+        return false;
+    }
+    match error {
+        AiError::Http { status, .. } => matches!(status, 429 | 500 | 502 | 503 | 504),
+        AiError::Network(_) => true,
+        _ => false,
+    }
+}
+
+fn is_throttled(error: &AiError) -> bool {
+    matches!(
+        error,
+        AiError::Http {
+            status: 429 | 503,
+            ..
+        }
+    )
+}
+
+/// Exponential backoff with "equal jitter": half the step is fixed, half
+/// scaled by `jitter` (0..1), so retries spread out without ever retrying
+/// immediately.
+fn backoff_delay(attempt: u32, jitter: f64) -> Duration {
+    let step = BACKOFF_BASE
+        .saturating_mul(1u32 << attempt.min(16))
+        .min(BACKOFF_CAP);
+    let half = step / 2;
+    half + half.mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// The provider's `Retry-After` when it sent one, else the backoff step.
+fn retry_delay(error: &AiError, attempt: u32, jitter: f64) -> Duration {
+    match error {
+        AiError::Http {
+            retry_after: Some(d),
+            ..
+        } => (*d).min(RETRY_AFTER_CAP),
+        _ => backoff_delay(attempt, jitter),
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    parse_retry_after_at(value, chrono::Utc::now())
+}
+
+fn parse_retry_after_at(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - now;
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+fn is_cancelled(cancel: Option<&CancelToken>) -> bool {
+    cancel.is_some_and(CancelToken::is_cancelled)
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<BucketKey, Bucket>> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Blocks until the bucket has a token, a free slot and no cooldown,
+    /// or until `cancel` fires.
+    fn acquire(
+        &self,
+        key: &BucketKey,
+        cancel: Option<&CancelToken>,
+    ) -> Result<Permit<'_>, AiError> {
+        let mut buckets = self.lock();
+        loop {
+            if is_cancelled(cancel) {
+                return Err(AiError::Cancelled);
+            }
+            let now = Instant::now();
+            let bucket = buckets
+                .entry(key.clone())
+                .or_insert_with(|| Bucket::new(key.provider, now));
+            match bucket.wait_time(now) {
+                None => {
+                    bucket.tokens -= 1.0;
+                    bucket.in_flight += 1;
+                    bucket.total_requests += 1;
+                    return Ok(Permit {
+                        limiter: self,
+                        key: key.clone(),
+                    });
+                }
+                Some(wait) => {
+                    buckets = self
+                        .changed
+                        .wait_timeout(buckets, wait.min(CANCEL_POLL))
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+        }
+    }
+
+    /// Waits out `delay` before a retry, returning early if `cancel` fires.
+    fn wait(&self, delay: Duration, cancel: Option<&CancelToken>) -> Result<(), AiError> {
+        let deadline = Instant::now() + delay;
+        let mut buckets = self.lock();
+        loop {
+            if is_cancelled(cancel) {
+                return Err(AiError::Cancelled);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            buckets = self
+                .changed
+                .wait_timeout(buckets, (deadline - now).min(CANCEL_POLL))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn with_bucket(&self, key: &BucketKey, f: impl FnOnce(&mut Bucket)) {
+        if let Some(bucket) = self.lock().get_mut(key) {
+            f(bucket);
+        }
+    }
+
+    /// Run `request` under the limits for `provider`/`api_key`, retrying
+    /// throttled and transient failures with backoff.  `Retry-After` from
+    /// the provider overrides the computed delay and pauses every request
+    /// on that key, not just this one.  Waiting for a slot or a retry ends
+    /// with [`AiError::Cancelled`] once `cancel` fires; a request already
+    /// sent runs to completion.
+    pub fn execute<T>(
+        &self,
+        provider: ProviderKind,
+        api_key: Option<&str>,
+        cancel: Option<&CancelToken>,
+        mut request: impl FnMut() -> Result<T, AiError>,
+    ) -> Result<T, AiError> {
+        let key = BucketKey {
+            provider,
+            key_id: key_id(api_key),
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let _permit = self.acquire(&key, cancel)?;
+                request()
+            };
+
+            let error = match result {
+                Ok(value) => {
+                    self.with_bucket(&key, |b| b.on_success());
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+
+            let delay = retry_delay(&error, attempt, fastrand::f64());
+            let throttled = is_throttled(&error);
+            self.with_bucket(&key, |b| {
+                b.last_error = Some(error.to_string());
+                if throttled {
+                    b.on_throttled(Instant::now(), delay);
+                } else {
+                    b.success_streak = 0;
+                }
+            });
+            self.changed.notify_all();
+
+            attempt += 1;
+            if !is_retryable(&error) || attempt >= MAX_ATTEMPTS {
+                return Err(error);
+            }
+            eprintln!(
+                "[ai] {} request failed ({}); retry {}/{} in {:?}",
+                provider,
+                error,
+                attempt,
+                MAX_ATTEMPTS - 1,
+                delay
+            );
+            // Throttled requests wait out the shared cooldown in `acquire`.
+            if !throttled {
+                self.wait(delay, cancel)?;
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<RateLimitStatus> {
+        let now = Instant::now();
+        let mut buckets = self.lock();
+        let mut statuses: Vec<RateLimitStatus> = buckets
+            .iter_mut()
+            .map(|(key, bucket)| {
+                bucket.refill(now);
+                RateLimitStatus {
+                    provider: key.provider,
+                    key_id: key.key_id.clone(),
+                    requests_per_minute: bucket.refill_per_sec * 60.0,
+                    tokens_available: bucket.tokens,
+                    burst: bucket.burst,
+                    concurrency_limit: bucket.concurrency_limit,
+                    in_flight: bucket.in_flight,
+                    blocked_for_ms: bucket
+                        .blocked_until
+                        .map(|u| u.saturating_duration_since(now).as_millis() as u64)
+                        .unwrap_or(0),
+                    total_requests: bucket.total_requests,
+                    throttled_responses: bucket.throttled_responses,
+                    last_error: bucket.last_error.clone(),
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| {
+            (a.provider.to_string(), &a.key_id).cmp(&(b.provider.to_string(), &b.key_id))
+        });
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::thread;
+
+    fn http(status: u16, body: &str, retry_after: Option<Duration>) -> AiError {
+        AiError::Http {
+            status,
+            body: body.to_string(),
+            retry_after,
+        }
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_caps() {
+        assert_eq!(backoff_delay(0, 0.0), secs(0.5));
+        assert_eq!(backoff_delay(0, 1.0), secs(1.0));
+        assert_eq!(backoff_delay(3, 0.0), secs(4.0));
+        assert_eq!(backoff_delay(3, 0.5), secs(6.0));
+        assert_eq!(backoff_delay(3, 1.0), secs(8.0));
+        assert_eq!(backoff_delay(6, 1.0), BACKOFF_CAP);
+        assert_eq!(backoff_delay(40, 0.0), BACKOFF_CAP / 2);
+        // Out-of-range jitter never escapes the step.
+        assert_eq!(backoff_delay(1, 7.0), secs(2.0));
+    }
+
+    #[test]
+    fn retry_after_overrides_backoff_up_to_the_cap() {
+        let with_header = http(503, "", Some(secs(12.0)));
+        assert_eq!(retry_delay(&with_header, 0, 0.0), secs(12.0));
+        let too_long = http(429, "", Some(secs(3600.0)));
+        assert_eq!(retry_delay(&too_long, 0, 0.0), RETRY_AFTER_CAP);
+        let without = http(500, "", None);
+        assert_eq!(retry_delay(&without, 2, 0.0), secs(2.0));
+    }
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = chrono::DateTime::parse_from_rfc2822("Wed, 21 Oct 2026 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(parse_retry_after_at("120", now), Some(secs(120.0)));
+        assert_eq!(parse_retry_after_at(" 1.5 ", now), Some(secs(1.5)));
+        assert_eq!(parse_retry_after_at("0", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after_at("-3", now), None);
+        assert_eq!(parse_retry_after_at("NaN", now), None);
+        assert_eq!(parse_retry_after_at("soon", now), None);
+        assert_eq!(
+            parse_retry_after_at("Wed, 21 Oct 2026 07:28:30 GMT", now),
+            Some(secs(30.0))
+        );
+        assert_eq!(
+            parse_retry_after_at("Wed, 21 Oct 2026 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn tokens_refill_at_the_bucket_rate() {
+        let t0 = Instant::now();
+        let mut bucket = Bucket::new(ProviderKind::Google, t0);
+        assert_eq!(bucket.wait_time(t0), None);
+
+        bucket.tokens = 0.0;
+        assert_eq!(bucket.wait_time(t0), Some(secs(2.0)));
+        assert_eq!(bucket.wait_time(t0 + secs(1.0)), Some(secs(1.0)));
+        assert_eq!(bucket.wait_time(t0 + secs(2.0)), None);
+        // Refill stops at the burst size.
+        bucket.refill(t0 + secs(60.0));
+        assert_eq!(bucket.tokens, bucket.burst);
+    }
+
+    #[test]
+    fn full_concurrency_waits_for_a_permit() {
+        let t0 = Instant::now();
+        let mut bucket = Bucket::new(ProviderKind::Openai, t0);
+        bucket.in_flight = bucket.concurrency_limit;
+        assert_eq!(bucket.wait_time(t0), Some(secs(1.0)));
+        bucket.in_flight -= 1;
+        assert_eq!(bucket.wait_time(t0), None);
+    }
+
+    #[test]
+    fn successes_ramp_up_to_the_maximum() {
+        let mut bucket = Bucket::new(ProviderKind::Openai, Instant::now());
+        for _ in 0..RAMP_UP_STREAK - 1 {
+            bucket.on_success();
+        }
+        assert_eq!(bucket.concurrency_limit, 4);
+        assert_eq!(bucket.refill_per_sec, 2.0);
+
+        bucket.on_success();
+        assert_eq!(bucket.concurrency_limit, 5);
+        assert_eq!(bucket.refill_per_sec, 2.5);
+        assert_eq!(bucket.success_streak, 0);
+
+        for _ in 0..RAMP_UP_STREAK * 100 {
+            bucket.on_success();
+        }
+        assert_eq!(bucket.concurrency_limit, bucket.max_concurrency);
+        assert_eq!(bucket.refill_per_sec, bucket.max_refill_per_sec);
+    }
+
+    #[test]
+    fn throttling_halves_the_pace_and_blocks_the_key() {
+        let t0 = Instant::now();
+        let mut bucket = Bucket::new(ProviderKind::Openai, t0);
+        bucket.success_streak = 3;
+        bucket.on_throttled(t0, secs(10.0));
+        assert_eq!(bucket.concurrency_limit, 2);
+        assert_eq!(bucket.refill_per_sec, 1.0);
+        assert_eq!(bucket.tokens, 0.0);
+        assert_eq!(bucket.success_streak, 0);
+        assert_eq!(bucket.throttled_responses, 1);
+        assert_eq!(bucket.wait_time(t0 + secs(4.0)), Some(secs(6.0)));
+
+        // A shorter cooldown never cuts an existing one short.
+        bucket.on_throttled(t0 + secs(1.0), secs(2.0));
+        assert_eq!(bucket.blocked_until, Some(t0 + secs(10.0)));
+        bucket.on_throttled(t0 + secs(1.0), secs(20.0));
+        assert_eq!(bucket.blocked_until, Some(t0 + secs(21.0)));
+
+        for _ in 0..20 {
+            bucket.on_throttled(t0, Duration::ZERO);
+        }
+        assert_eq!(bucket.concurrency_limit, 1);
+        assert_eq!(bucket.refill_per_sec, bucket.min_refill_per_sec);
+    }
+
+    #[test]
+    fn classifies_retryable_and_throttled_errors() {
+        for status in [429, 500, 502, 503, 504] {
+            assert!(is_retryable(&http(status, "", None)), "{}", status);
+        }
+        assert!(is_retryable(&AiError::Network("reset".into())));
+        assert!(!is_retryable(&http(400, "bad request", None)));
+        assert!(!is_retryable(&http(401, "", None)));
+        assert!(!is_retryable(&http(429, "insufficient_quota", None)));
+        assert!(!is_retryable(&AiError::Cancelled));
+
+        assert!(is_throttled(&http(429, "", None)));
+        assert!(is_throttled(&http(503, "", None)));
+        assert!(!is_throttled(&http(500, "", None)));
+    }
+
+    #[test]
+    fn execute_retries_transient_errors() {
+        let limiter = RateLimiter::new();
+        let calls = AtomicU32::new(0);
+        let result = limiter.execute(ProviderKind::Local, None, None, || {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(http(500, "", Some(Duration::ZERO))),
+                _ => Ok("done"),
+            }
+        });
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = limiter.execute(ProviderKind::Local, None, None, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(http(400, "", None))
+        });
+        assert!(matches!(result, Err(AiError::Http { status: 400, .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn cancel_interrupts_waits() {
+        let limiter = RateLimiter::new();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result: Result<(), _> =
+            limiter.execute(ProviderKind::Local, None, Some(&cancel), || {
+                panic!("a cancelled request must not be sent")
+            });
+        assert!(matches!(result, Err(AiError::Cancelled)));
+
+        let cancel = CancelToken::new();
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                cancel.cancel();
+            })
+        };
+        let started = Instant::now();
+        let result: Result<(), _> =
+            limiter.execute(ProviderKind::Local, None, Some(&cancel), || {
+                Err(http(500, "", Some(secs(60.0))))
+            });
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(AiError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,358 @@
+// Token usage and cost accounting.  Every successful provider response is
+// appended to a JSON Lines ledger in the data directory, priced from the
+// provider's own figure when it reports one (OpenRouter) or from a price
+// table the user can edit.
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::ProviderKind;
+
+lazy_static! {
+    /// Serialises appends so concurrent job workers don't interleave lines.
+    static ref LEDGER_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Token counts as reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// What the provider says it billed, in USD, when it says so
+    #[serde(default)]
+    pub reported_cost: Option<f64>,
+}
+
+/// USD per million tokens for one model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// Prices keyed by model id.  A model matches the longest key it starts
+/// with, so `gemini-2.0-flash` also prices `gemini-2.0-flash-001`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceTable {
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+/// List prices for the defaults and common alternatives.  Prices change;
+/// the table is saved to the config directory for the user to correct.
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("gemini-2.0-flash-lite", 0.075, 0.30),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-2.5-flash-lite", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+];
+
+impl PriceTable {
+    pub fn defaults() -> Self {
+        PriceTable {
+            models: DEFAULT_PRICES
+                .iter()
+                .map(|&(model, input, output)| {
+                    (
+                        model.to_string(),
+                        ModelPrice {
+                            input_per_million: input,
+                            output_per_million: output,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Price for `model`, trying the OpenRouter-style `vendor/model` id and
+    /// then the bare model name.
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        let model = model.trim().to_lowercase();
+        let bare = model.rsplit('/').next().unwrap_or(&model).to_string();
+        [model, bare].iter().find_map(|name| {
+            self.models
+                .iter()
+                .filter(|(key, _)| name.starts_with(&key.to_lowercase()))
+                .max_by_key(|(key, _)| key.len())
+                .map(|(_, price)| *price)
+        })
+    }
+}
+
+fn get_price_table_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("descify").join("prices.json"))
+}
+
+/// The saved price table, or the built-in one when none was saved
+pub fn load_price_table() -> PriceTable {
+    get_price_table_path()
+        .and_then(|p| fs::read(p).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_else(PriceTable::defaults)
+}
+
+pub fn save_price_table(table: &PriceTable) -> Result<(), String> {
+    for (model, price) in &table.models {
+        let valid = |v: f64| v.is_finite() && v >= 0.0;
+        if !valid(price.input_per_million) || !valid(price.output_per_million) {
+            return Err(format!("Invalid price for {}", model));
+        }
+    }
+    let path = get_price_table_path()
+        .ok_or_else(|| "Could not determine the config directory".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let data = serde_json::to_vec_pretty(table)
+        .map_err(|e| format!("Failed to serialize price table: {}", e))?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Where a record's cost came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostSource {
+    /// Billed amount reported by the provider
+    Provider,
+    /// Computed from the price table
+    PriceTable,
+    /// Local servers cost nothing per request
+    Free,
+    /// No usage or no price for the model
+    Unknown,
+}
+
+/// One line of the ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    /// Local date (YYYY-MM-DD), for per-day totals
+    pub date: String,
+    /// Batch job the request belonged to; `None` for one-off generations
+    #[serde(default)]
+    pub job_id: Option<String>,
+    pub provider: ProviderKind,
+    pub model: String,
+    #[serde(default)]
+    pub file_path: Option<String>,
+    pub images: u32,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: Option<f64>,
+    pub cost_source: CostSource,
+}
+
+impl UsageRecord {
+    pub fn new(
+        provider: ProviderKind,
+        model: &str,
+        images: u32,
+        usage: Option<TokenUsage>,
+        prices: &PriceTable,
+    ) -> Self {
+        let usage_tokens = usage.unwrap_or_default();
+        let (cost_usd, cost_source) = match (provider, usage) {
+            (ProviderKind::Local | ProviderKind::Ollama, _) => (Some(0.0), CostSource::Free),
+            (
+                _,
+                Some(TokenUsage {
+                    reported_cost: Some(cost),
+                    ..
+                }),
+            ) => (Some(cost), CostSource::Provider),
+            (_, Some(u)) => match prices.price_for(model) {
+                Some(price) => (
+                    Some(
+                        (u.input_tokens as f64 * price.input_per_million
+                            + u.output_tokens as f64 * price.output_per_million)
+                            / 1_000_000.0,
+                    ),
+                    CostSource::PriceTable,
+                ),
+                None => (None, CostSource::Unknown),
+            },
+            (_, None) => (None, CostSource::Unknown),
+        };
+
+        UsageRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            job_id: None,
+            provider,
+            model: model.to_string(),
+            file_path: None,
+            images,
+            input_tokens: usage_tokens.input_tokens,
+            output_tokens: usage_tokens.output_tokens,
+            cost_usd,
+            cost_source,
+        }
+    }
+}
+
+fn get_ledger_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("descify").join("usage.jsonl"))
+}
+
+/// Append `record` to the ledger.  Accounting must never fail a
+/// generation, so errors are only logged.
+pub fn record_usage(record: &UsageRecord) {
+    let Some(path) = get_ledger_path() else {
+        return;
+    };
+    let line = match serde_json::to_string(record) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("[usage] Failed to serialize usage record: {}", e);
+            return;
+        }
+    };
+
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = result {
+        eprintln!("[usage] Failed to write {:?}: {}", path, e);
+    }
+}
+
+/// Inclusive date range (YYYY-MM-DD); open ends are unbounded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageRange {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    /// Only count requests made by this batch job
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub images: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Sum of the priced requests, in USD
+    pub cost_usd: f64,
+    /// Requests that couldn't be priced and are missing from `cost_usd`
+    pub unpriced_requests: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.images += record.images as u64;
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        match record.cost_usd {
+            Some(cost) => self.cost_usd += cost,
+            None => self.unpriced_requests += 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub date: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub provider: ProviderKind,
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub range: UsageRange,
+    pub totals: UsageTotals,
+    /// Oldest day first
+    pub by_day: Vec<DailyUsage>,
+    /// Most expensive first
+    pub by_model: Vec<ModelUsage>,
+}
+
+/// Totals over the ledger for `range`, per day and per model
+pub fn usage_report(range: UsageRange) -> Result<UsageReport, String> {
+    let mut totals = UsageTotals::default();
+    let mut by_day: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    let mut by_model: HashMap<(ProviderKind, String), UsageTotals> = HashMap::new();
+
+    let path = get_ledger_path().filter(|p| p.exists());
+    if let Some(path) = path {
+        let file =
+            fs::File::open(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            // A torn last line after a crash shouldn't hide the rest.
+            let Ok(record) = serde_json::from_str::<UsageRecord>(&line) else {
+                continue;
+            };
+            let in_range = range.from.as_ref().is_none_or(|from| record.date >= *from)
+                && range.to.as_ref().is_none_or(|to| record.date <= *to)
+                && range
+                    .job_id
+                    .as_ref()
+                    .is_none_or(|id| record.job_id.as_ref() == Some(id));
+            if !in_range {
+                continue;
+            }
+            totals.add(&record);
+            by_day.entry(record.date.clone()).or_default().add(&record);
+            by_model
+                .entry((record.provider, record.model.clone()))
+                .or_default()
+                .add(&record);
+        }
+    }
+
+    let mut by_model: Vec<ModelUsage> = by_model
+        .into_iter()
+        .map(|((provider, model), totals)| ModelUsage {
+            provider,
+            model,
+            totals,
+        })
+        .collect();
+    by_model.sort_by(|a, b| {
+        b.totals
+            .cost_usd
+            .total_cmp(&a.totals.cost_usd)
+            .then_with(|| b.totals.requests.cmp(&a.totals.requests))
+    });
+
+    Ok(UsageReport {
+        range,
+        totals,
+        by_day: by_day
+            .into_iter()
+            .map(|(date, totals)| DailyUsage { date, totals })
+            .collect(),
+        by_model,
+    })
+}
@@ -1,49 +1,58 @@
 use crate::models::metadata::{EmbedMetadataRequest, EmbedMetadataResult, ExifData};
+use crate::services::process::{self, CancelToken, ProcessError, RunOptions};
+use crate::services::tools::{tool_path, ExternalTool};
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
-/// Get the path to the bundled exiftool binary
+/// Rewriting a large video's atoms can take a while; reading never should.
+const EXIFTOOL_WRITE_TIMEOUT: Duration = Duration::from_secs(120);
+const EXIFTOOL_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Get the path to the exiftool binary, falling back to the bare name so
+/// spawn errors still mention exiftool when nothing was found.
 pub fn get_exiftool_path() -> PathBuf {
-    // Try to find exiftool in the following order:
-    // 1. Bundled with the app (in resources)
-    // 2. System PATH (fallback)
-
-    // Get the resource directory path
-    if let Ok(resource_dir) = std::env::current_exe().and_then(|exe_path| {
-        exe_path
-            .parent()
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No parent"))
-            .map(|p| p.to_path_buf())
-    }) {
-        // Check for platform-specific exiftool binary
-        #[cfg(target_os = "windows")]
-        let exiftool_name = "exiftool.exe";
-
-        #[cfg(not(target_os = "windows"))]
-        let exiftool_name = "exiftool";
-
-        // Try in the same directory as the executable
-        let bundled_path = resource_dir.join(exiftool_name);
-        if bundled_path.exists() {
-            return bundled_path;
-        }
+    tool_path(ExternalTool::Exiftool).unwrap_or_else(|| PathBuf::from("exiftool"))
+}
+
+/// File container families that need different metadata tag mappings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataContainer {
+    /// Still images: XMP, IPTC and EXIF
+    Image,
+    /// MP4/MOV family: XMP-dc plus QuickTime ItemList/Keys/UserData atoms,
+    /// which is what video agencies and players actually read
+    QuickTime,
+}
+
+impl MetadataContainer {
+    pub fn from_path(file_path: &str) -> Self {
+        let extension = Path::new(file_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
 
-        // Try in a resources subdirectory
-        let resources_path = resource_dir.join("resources").join(exiftool_name);
-        if resources_path.exists() {
-            return resources_path;
+        match extension.as_deref() {
+            Some("mp4") | Some("mov") | Some("m4v") | Some("qt") | Some("3gp") => {
+                MetadataContainer::QuickTime
+            }
+            _ => MetadataContainer::Image,
         }
     }
-
-    // Fallback to system PATH
-    PathBuf::from("exiftool")
 }
 
 /// Build exiftool command with metadata arguments
 pub fn build_exiftool_command(exiftool_path: &PathBuf, request: &EmbedMetadataRequest) -> Command {
     let mut cmd = Command::new(exiftool_path);
 
+    if MetadataContainer::from_path(&request.file_path) == MetadataContainer::QuickTime {
+        add_quicktime_metadata_args(&mut cmd, request);
+        cmd.arg("-overwrite_original");
+        cmd.arg(&request.file_path);
+        return cmd;
+    }
+
     // Add title tags if provided
     if let Some(ref title) = request.title {
         if !title.trim().is_empty() {
@@ -93,13 +102,59 @@ pub fn build_exiftool_command(exiftool_path: &PathBuf, request: &EmbedMetadataRe
     cmd
 }
 
+/// Add XMP-dc and QuickTime atom arguments for MP4/MOV files
+fn add_quicktime_metadata_args(cmd: &mut Command, request: &EmbedMetadataRequest) {
+    if let Some(ref title) = request.title {
+        if !title.trim().is_empty() {
+            cmd.arg(format!("-XMP-dc:Title={}", title));
+            cmd.arg(format!("-ItemList:Title={}", title));
+            cmd.arg(format!("-Keys:Title={}", title));
+            cmd.arg(format!("-UserData:Title={}", title));
+        }
+    }
+
+    if let Some(ref description) = request.description {
+        if !description.trim().is_empty() {
+            cmd.arg(format!("-XMP-dc:Description={}", description));
+            cmd.arg(format!("-ItemList:Description={}", description));
+            cmd.arg(format!("-Keys:Description={}", description));
+            cmd.arg(format!("-UserData:Description={}", description));
+        }
+    }
+
+    if let Some(ref keywords) = request.keywords {
+        let keyword_list: Vec<&str> = keywords
+            .split(',')
+            .map(|k| k.trim())
+            .filter(|k| !k.is_empty())
+            .collect();
+
+        if !keyword_list.is_empty() {
+            for keyword in &keyword_list {
+                cmd.arg(format!("-XMP-dc:Subject={}", keyword));
+            }
+
+            // QuickTime keyword atoms are single strings.  UserData has no
+            // writable keywords tag, so the Keys atom carries them, with
+            // QuickTime:Keywords filling whichever group exiftool prefers.
+            let joined = keyword_list.join(", ");
+            cmd.arg(format!("-QuickTime:Keywords={}", joined));
+            cmd.arg(format!("-Keys:Keywords={}", joined));
+        }
+    }
+}
+
 /// Execute exiftool command and return result
 pub fn execute_exiftool(
-    mut cmd: Command,
+    cmd: Command,
     request: &EmbedMetadataRequest,
     exiftool_path: &PathBuf,
+    cancel: Option<CancelToken>,
 ) -> Result<EmbedMetadataResult, String> {
-    match cmd.output() {
+    let options = RunOptions::with_timeout(EXIFTOOL_WRITE_TIMEOUT)
+        .max_stdout(1024 * 1024)
+        .cancel_token(cancel);
+    match process::run(cmd, &options) {
         Ok(output) => {
             let _stdout = String::from_utf8_lossy(&output.stdout);
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -130,7 +185,7 @@ pub fn execute_exiftool(
             }
         }
         Err(e) => {
-            let error_msg = if e.kind() == std::io::ErrorKind::NotFound {
+            let error_msg = if matches!(e, ProcessError::NotFound { .. }) {
                 format!(
                     "Failed to execute exiftool: {} - ExifTool not found. Please install ExifTool or ensure it's bundled with the application. Tried path: {:?}",
                     e,
@@ -179,6 +234,69 @@ pub fn has_metadata(request: &EmbedMetadataRequest) -> bool {
     request.title.is_some() || request.description.is_some() || request.keywords.is_some()
 }
 
+/// First non-empty value among `names`, ignoring any `Group:` prefix.
+/// Composite tags win over their EXIF sources (e.g. signed GPS values).
+fn tag_value<'a>(metadata: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    let object = metadata.as_object()?;
+    names.iter().find_map(|name| {
+        let matches = |key: &String| key.rsplit(':').next() == Some(*name);
+        object
+            .iter()
+            .filter(|(k, _)| matches(k))
+            .max_by_key(|(k, _)| k.starts_with("Composite:"))
+            .map(|(_, v)| v)
+            .filter(|v| !v.is_null() && v.as_str().is_none_or(|s| !s.trim().is_empty()))
+    })
+}
+
+fn tag_string(metadata: &Value, names: &[&str]) -> Option<String> {
+    match tag_value(metadata, names)? {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn tag_number(metadata: &Value, names: &[&str]) -> Option<f64> {
+    match tag_value(metadata, names)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// The EXIF copy of a GPS coordinate is unsigned with a separate N/S or
+/// E/W reference; apply it unless the value is already signed.
+fn gps_coordinate(metadata: &Value, name: &str, negative_ref: char) -> Option<f64> {
+    let value = tag_number(metadata, &[name])?;
+    let reference = tag_string(metadata, &[&format!("{}Ref", name)]);
+    let negative = reference
+        .and_then(|r| r.chars().next())
+        .is_some_and(|c| c.eq_ignore_ascii_case(&negative_ref));
+    Some(if negative && value > 0.0 {
+        -value
+    } else {
+        value
+    })
+}
+
+/// `2023:06:14 18:22:10+02:00` -> `2023-06-14 18:22:10`.  Zeroed dates
+/// (`0000:00:00`) from cameras without a clock are dropped.
+fn normalize_exif_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    let (date, time) = value.split_once(' ').unwrap_or((value, ""));
+    let date = date.replace(':', "-");
+    if date.len() != 10 || date.starts_with("0000") {
+        return None;
+    }
+    let time: String = time.chars().take(8).collect();
+    Some(if time.is_empty() {
+        date
+    } else {
+        format!("{} {}", date, time)
+    })
+}
+
 /// Read EXIF metadata from an image/video file using exiftool
 pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
     // Validate file exists
@@ -198,10 +316,15 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
     let mut cmd = Command::new(&exiftool_path);
     cmd.arg("-json");
     cmd.arg("-n"); // No conversion (show raw values)
+    if MetadataContainer::from_path(file_path) == MetadataContainer::QuickTime {
+        // Group-prefix tag names so the ItemList/Keys/UserData copies of
+        // Title, Description and Keywords can be told apart
+        cmd.arg("-G1");
+    }
     cmd.arg(file_path);
 
     // Execute command
-    match cmd.output() {
+    match process::run(cmd, &RunOptions::with_timeout(EXIFTOOL_READ_TIMEOUT)) {
         Ok(output) => {
             if !output.status.success() {
                 let stderr = String::from_utf8_lossy(&output.stderr);
@@ -225,9 +348,7 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
                 None => {
                     return Ok(ExifData {
                         file_path: file_path.to_string(),
-                        title: None,
-                        description: None,
-                        keywords: None,
+                        ..Default::default()
                     })
                 }
             };
@@ -237,6 +358,11 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
                 // XMP fields
                 .get("XMP:Title")
                 .or_else(|| metadata.get("Title"))
+                .or_else(|| metadata.get("XMP-dc:Title"))
+                // QuickTime atoms (MP4/MOV)
+                .or_else(|| metadata.get("Keys:Title"))
+                .or_else(|| metadata.get("ItemList:Title"))
+                .or_else(|| metadata.get("UserData:Title"))
                 // IPTC fields
                 .or_else(|| metadata.get("IPTC:ObjectName"))
                 .or_else(|| metadata.get("ObjectName"))
@@ -254,6 +380,11 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
                 // XMP fields
                 .get("XMP:Description")
                 .or_else(|| metadata.get("Description"))
+                .or_else(|| metadata.get("XMP-dc:Description"))
+                // QuickTime atoms (MP4/MOV)
+                .or_else(|| metadata.get("Keys:Description"))
+                .or_else(|| metadata.get("ItemList:Description"))
+                .or_else(|| metadata.get("UserData:Description"))
                 // IPTC fields
                 .or_else(|| metadata.get("IPTC:Caption-Abstract"))
                 .or_else(|| metadata.get("Caption-Abstract"))
@@ -278,6 +409,9 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
                 // DC fields
                 .or_else(|| metadata.get("XMP-dc:Subject"))
                 .or_else(|| metadata.get("dc:Subject"))
+                // QuickTime atoms (MP4/MOV)
+                .or_else(|| metadata.get("Keys:Keywords"))
+                .or_else(|| metadata.get("UserData:Keywords"))
                 .and_then(|v| {
                     if let Some(arr) = v.as_array() {
                         // If it's an array, join with commas
@@ -302,15 +436,41 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
                 title, description, keywords
             );
 
+            // Capture details for prompt templates.  With -G1 (QuickTime)
+            // tag names carry a group prefix, so match on the bare name.
+            let capture_date = tag_string(
+                metadata,
+                &[
+                    "DateTimeOriginal",
+                    "CreateDate",
+                    "CreationDate",
+                    "MediaCreateDate",
+                ],
+            )
+            .and_then(|d| normalize_exif_date(&d));
+
             Ok(ExifData {
                 file_path: file_path.to_string(),
                 title,
                 description,
                 keywords,
+                camera_make: tag_string(metadata, &["Make", "AndroidMake"]),
+                camera_model: tag_string(metadata, &["Model", "AndroidModel"]),
+                lens: tag_string(metadata, &["LensModel", "Lens", "LensID", "LensType"]),
+                capture_date,
+                gps_latitude: gps_coordinate(metadata, "GPSLatitude", 'S'),
+                gps_longitude: gps_coordinate(metadata, "GPSLongitude", 'W'),
+                sublocation: tag_string(metadata, &["Sub-location", "Location", "Sublocation"]),
+                city: tag_string(metadata, &["City"]),
+                state: tag_string(metadata, &["Province-State", "State"]),
+                country: tag_string(
+                    metadata,
+                    &["Country", "Country-PrimaryLocationName", "CountryName"],
+                ),
             })
         }
         Err(e) => {
-            if e.kind() == std::io::ErrorKind::NotFound {
+            if matches!(e, ProcessError::NotFound { .. }) {
                 Err(format!(
                     "ExifTool not found. Tried path: {:?}",
                     exiftool_path
@@ -321,3 +481,96 @@ pub fn read_exif_metadata(file_path: &str) -> Result<ExifData, String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(file_path: &str) -> Vec<String> {
+        let request = EmbedMetadataRequest {
+            file_path: file_path.to_string(),
+            title: Some("Harbour at dusk".to_string()),
+            description: Some("Boats moored in a calm harbour".to_string()),
+            keywords: Some("harbour, boats ,, dusk".to_string()),
+        };
+        build_exiftool_command(&PathBuf::from("exiftool"), &request)
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn detects_quicktime_containers() {
+        for path in ["a.mp4", "b.MOV", "c.m4v", "d.qt", "e.3gp"] {
+            assert_eq!(
+                MetadataContainer::from_path(path),
+                MetadataContainer::QuickTime
+            );
+        }
+        for path in ["a.jpg", "b.png", "c.webm", "no_extension"] {
+            assert_eq!(MetadataContainer::from_path(path), MetadataContainer::Image);
+        }
+    }
+
+    #[test]
+    fn videos_get_xmp_and_quicktime_tags() {
+        let expected = [
+            "-XMP-dc:Title=Harbour at dusk",
+            "-ItemList:Title=Harbour at dusk",
+            "-Keys:Title=Harbour at dusk",
+            "-UserData:Title=Harbour at dusk",
+            "-XMP-dc:Description=Boats moored in a calm harbour",
+            "-ItemList:Description=Boats moored in a calm harbour",
+            "-Keys:Description=Boats moored in a calm harbour",
+            "-UserData:Description=Boats moored in a calm harbour",
+            "-XMP-dc:Subject=harbour",
+            "-XMP-dc:Subject=boats",
+            "-XMP-dc:Subject=dusk",
+            "-QuickTime:Keywords=harbour, boats, dusk",
+            "-Keys:Keywords=harbour, boats, dusk",
+            "-overwrite_original",
+        ];
+        assert_eq!(args("/clips/harbour.mp4")[..14], expected);
+        assert_eq!(args("/clips/harbour.mp4")[14], "/clips/harbour.mp4");
+        assert_eq!(args("/clips/harbour.MOV")[..14], expected);
+        assert!(!args("/clips/harbour.mov")
+            .iter()
+            .any(|a| a.starts_with("-UserData:Keywords")));
+    }
+
+    #[test]
+    fn images_get_xmp_iptc_and_exif_tags() {
+        assert_eq!(
+            args("/photos/harbour.jpg"),
+            [
+                "-XMP:Title=Harbour at dusk",
+                "-IPTC:ObjectName=Harbour at dusk",
+                "-EXIF:ImageDescription=Harbour at dusk",
+                "-XMP:Description=Boats moored in a calm harbour",
+                "-EXIF:ImageDescription=Boats moored in a calm harbour",
+                "-IPTC:Caption-Abstract=Boats moored in a calm harbour",
+                "-XMP:Subject=harbour",
+                "-XMP:Subject=boats",
+                "-XMP:Subject=dusk",
+                "-IPTC:Keywords=harbour, boats ,, dusk",
+                "-overwrite_original",
+                "/photos/harbour.jpg",
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_fields_are_left_alone() {
+        let request = EmbedMetadataRequest {
+            file_path: "clip.mov".to_string(),
+            title: Some("  ".to_string()),
+            description: None,
+            keywords: Some(" , ".to_string()),
+        };
+        let args: Vec<String> = build_exiftool_command(&PathBuf::from("exiftool"), &request)
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(args, ["-overwrite_original", "clip.mov"]);
+    }
+}
@@ -1,45 +1,285 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-pub fn is_gpu_available() -> bool {
-    check_nvidia() || check_amd()
+use crate::services::process::{self, RunOptions};
+
+/// Probes run once at startup; a wedged driver must not stall launch.
+const DETECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variable that forces a backend instead of auto-detection.
+const BACKEND_OVERRIDE_ENV: &str = "DESCIFY_VIDEO_BACKEND";
+
+/// Hardware decoding pipeline used for video frame extraction.  Each backend
+/// pairs its `-hwaccel` arguments with a filter chain that understands the
+/// surface type it produces, then downloads to system memory for the JPEG
+/// encoder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoBackend {
+    Cuda,
+    Vaapi,
+    VideoToolbox,
+    #[default]
+    Cpu,
 }
 
-fn check_nvidia() -> bool {
-    if let Ok(output) = Command::new("nvidia-smi")
-        .arg("--query-gpu=name")
-        .arg("--format=csv,noheader")
-        .output()
-    {
-        return output.status.success() && !output.stdout.is_empty();
+impl VideoBackend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "cuda" | "nvidia" => Some(VideoBackend::Cuda),
+            "vaapi" | "amd" => Some(VideoBackend::Vaapi),
+            "videotoolbox" => Some(VideoBackend::VideoToolbox),
+            "cpu" | "none" => Some(VideoBackend::Cpu),
+            _ => None,
+        }
+    }
+
+    pub fn is_hardware(&self) -> bool {
+        *self != VideoBackend::Cpu
+    }
+
+    /// Input arguments placed before `-i`.
+    pub fn hwaccel_args(&self) -> Vec<&'static str> {
+        match self {
+            VideoBackend::Cuda => vec!["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"],
+            VideoBackend::Vaapi => vec!["-hwaccel", "vaapi", "-hwaccel_output_format", "vaapi"],
+            // VideoToolbox frames are downloaded automatically.
+            VideoBackend::VideoToolbox => vec!["-hwaccel", "videotoolbox"],
+            VideoBackend::Cpu => Vec::new(),
+        }
+    }
+
+    /// Filter chain that scales a decoded frame to fit `width`x`height`,
+    /// letterboxing it with black so the output is exactly that size.  The
+    /// GPU scalers keep both sides even, since nv12 rejects odd sizes.
+    pub fn scale_filter(&self, width: u32, height: u32) -> String {
+        let pad = format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2:black", width, height);
+        match self {
+            VideoBackend::Cuda => format!(
+                "scale_cuda={}:{}:force_original_aspect_ratio=decrease:force_divisible_by=2,hwdownload,format=nv12,{}",
+                width, height, pad
+            ),
+            VideoBackend::Vaapi => format!(
+                "scale_vaapi=w={}:h={}:force_original_aspect_ratio=decrease:force_divisible_by=2:format=nv12,hwdownload,format=nv12,{}",
+                width, height, pad
+            ),
+            VideoBackend::VideoToolbox | VideoBackend::Cpu => format!(
+                "scale={}:{}:force_original_aspect_ratio=decrease,{}",
+                width, height, pad
+            ),
+        }
     }
-    false
 }
 
-fn check_amd() -> bool {
-    if cfg!(target_os = "linux") {
-        if let Ok(output) = Command::new("lsmod").output() {
-            return output.status.success()
-                && String::from_utf8_lossy(&output.stdout).contains("radeon");
+/// What the machine and the bundled ffmpeg can do, detected once at startup.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HardwareCapabilities {
+    pub nvidia_gpu: bool,
+    pub amd_gpu: bool,
+    /// Methods listed by `ffmpeg -hwaccels`; empty when ffmpeg is missing.
+    pub ffmpeg_hwaccels: Vec<String>,
+    /// Best backend supported by both the hardware and ffmpeg.
+    pub detected_backend: VideoBackend,
+}
+
+impl HardwareCapabilities {
+    /// Probes the GPU and ffmpeg.  Runs `nvidia-smi`, `lsmod` and ffmpeg, so
+    /// call it once and keep the result rather than per video.
+    ///
+    /// Setting `DESCIFY_VIDEO_BACKEND` to `cuda`, `vaapi`, `videotoolbox` or
+    /// `cpu` overrides the choice, e.g. to exercise the CPU path on a GPU
+    /// machine.
+    pub fn detect(ffmpeg: Option<&Path>) -> Self {
+        let override_name = std::env::var(BACKEND_OVERRIDE_ENV).ok();
+        Self::from_probes(
+            override_name.as_deref(),
+            check_nvidia(),
+            check_amd(),
+            ffmpeg.map(list_ffmpeg_hwaccels).unwrap_or_default(),
+        )
+    }
+
+    /// Builds the capabilities from probe results already gathered by
+    /// [`detect`](Self::detect), with `override_name` taken from the
+    /// environment.
+    fn from_probes(
+        override_name: Option<&str>,
+        nvidia_gpu: bool,
+        amd_gpu: bool,
+        ffmpeg_hwaccels: Vec<String>,
+    ) -> Self {
+        let detected_backend = select_backend(override_name, nvidia_gpu, amd_gpu, &ffmpeg_hwaccels);
+
+        Self {
+            nvidia_gpu,
+            amd_gpu,
+            ffmpeg_hwaccels,
+            detected_backend,
         }
     }
-    false
 }
 
-pub fn get_ffmpeg_hwaccel_args() -> Vec<&'static str> {
-    let mut args = Vec::new();
+/// Picks the backend from the probe results.  A recognised override name
+/// wins; an unknown one is ignored.
+fn select_backend(
+    override_name: Option<&str>,
+    nvidia_gpu: bool,
+    amd_gpu: bool,
+    ffmpeg_hwaccels: &[String],
+) -> VideoBackend {
+    if let Some(backend) = override_name.and_then(VideoBackend::from_name) {
+        return backend;
+    }
+    let supports = |name: &str| ffmpeg_hwaccels.iter().any(|h| h == name);
+    if nvidia_gpu && supports("cuda") {
+        VideoBackend::Cuda
+    } else if cfg!(target_os = "macos") && supports("videotoolbox") {
+        VideoBackend::VideoToolbox
+    } else if cfg!(target_os = "linux") && amd_gpu && supports("vaapi") {
+        VideoBackend::Vaapi
+    } else {
+        VideoBackend::Cpu
+    }
+}
 
-    if check_nvidia() {
-        args.push("-hwaccel");
-        args.push("cuda");
-    } else if cfg!(target_os = "macos") {
-        args.push("-hwaccel");
-        args.push("auto");
-    } else if cfg!(target_os = "linux") && check_amd() {
-        args.push("-hwaccel");
-        args.push("vaapi");
+/// Snapshot returned to the frontend by `get_hardware_capabilities`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareStatus {
+    #[serde(flatten)]
+    pub capabilities: HardwareCapabilities,
+    pub force_cpu: bool,
+    /// Backend video jobs actually use, after the user override.
+    pub active_backend: VideoBackend,
+    /// The startup probe is still running; the capabilities are all unset
+    /// and video jobs use the CPU until it finishes.
+    pub detecting: bool,
+}
+
+/// Persisted hardware settings
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HardwareConfig {
+    #[serde(default)]
+    force_cpu: bool,
+}
+
+fn get_hardware_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("descify").join("hardware.json"))
+}
+
+fn load_hardware_config() -> HardwareConfig {
+    get_hardware_config_path()
+        .and_then(|p| fs::read(p).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_hardware_config(config: &HardwareConfig) -> Result<(), String> {
+    let path = get_hardware_config_path()
+        .ok_or_else(|| "Could not determine the config directory".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
     }
+    let data = serde_json::to_vec_pretty(config)
+        .map_err(|e| format!("Failed to serialize hardware config: {}", e))?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
 
-    args
+/// Managed state holding the startup capability probe and the user's
+/// "force CPU" override.
+pub struct HardwareState {
+    /// Filled in by the background probe
+    capabilities: Arc<OnceLock<HardwareCapabilities>>,
+    force_cpu: AtomicBool,
+}
+
+impl HardwareState {
+    /// Starts the capability probe on a background thread, so window
+    /// creation doesn't wait for nvidia-smi, lsmod and ffmpeg.  The ffmpeg
+    /// lookup runs there too, since it may launch ffmpeg to check its
+    /// version.
+    pub fn detect_in_background(find_ffmpeg: fn() -> Option<PathBuf>) -> Self {
+        let capabilities = Arc::new(OnceLock::new());
+        let probe = Arc::clone(&capabilities);
+        std::thread::spawn(move || {
+            let _ = probe.set(HardwareCapabilities::detect(find_ffmpeg().as_deref()));
+        });
+        Self {
+            capabilities,
+            force_cpu: AtomicBool::new(load_hardware_config().force_cpu),
+        }
+    }
+
+    /// The backend video extraction should use right now.
+    pub fn backend(&self) -> VideoBackend {
+        if self.force_cpu.load(Ordering::Relaxed) {
+            return VideoBackend::Cpu;
+        }
+        self.capabilities
+            .get()
+            .map(|c| c.detected_backend)
+            .unwrap_or(VideoBackend::Cpu)
+    }
+
+    /// Saves the override before applying it, so it survives a restart.
+    pub fn set_force_cpu(&self, force_cpu: bool) -> Result<(), String> {
+        save_hardware_config(&HardwareConfig { force_cpu })?;
+        self.force_cpu.store(force_cpu, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn status(&self) -> HardwareStatus {
+        let capabilities = self.capabilities.get();
+        HardwareStatus {
+            capabilities: capabilities.cloned().unwrap_or_default(),
+            force_cpu: self.force_cpu.load(Ordering::Relaxed),
+            active_backend: self.backend(),
+            detecting: capabilities.is_none(),
+        }
+    }
+}
+
+/// Parses `ffmpeg -hwaccels`, which prints a header line followed by one
+/// method name per line.
+fn list_ffmpeg_hwaccels(ffmpeg: &Path) -> Vec<String> {
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-hide_banner", "-hwaccels"]);
+    match process::run(cmd, &RunOptions::with_timeout(DETECTION_TIMEOUT)) {
+        Ok(o) if o.success() => parse_hwaccels(&String::from_utf8_lossy(&o.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+fn parse_hwaccels(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.ends_with(':'))
+        .map(|l| l.to_string())
+        .collect()
+}
+
+fn check_nvidia() -> bool {
+    let mut cmd = Command::new("nvidia-smi");
+    cmd.arg("--query-gpu=name").arg("--format=csv,noheader");
+    match process::run(cmd, &RunOptions::with_timeout(DETECTION_TIMEOUT)) {
+        Ok(output) => output.success() && !output.stdout.is_empty(),
+        Err(_) => false,
+    }
+}
+
+fn check_amd() -> bool {
+    if cfg!(target_os = "linux") {
+        let cmd = Command::new("lsmod");
+        if let Ok(output) = process::run(cmd, &RunOptions::with_timeout(DETECTION_TIMEOUT)) {
+            return output.success() && String::from_utf8_lossy(&output.stdout).contains("radeon");
+        }
+    }
+    false
 }
 
 #[allow(dead_code)]
@@ -84,3 +324,137 @@ pub fn get_video_encoder_args() -> Vec<&'static str> {
 
     args
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hwaccels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn hwaccel_args_per_backend() {
+        assert_eq!(
+            VideoBackend::Cuda.hwaccel_args(),
+            ["-hwaccel", "cuda", "-hwaccel_output_format", "cuda"]
+        );
+        assert_eq!(
+            VideoBackend::Vaapi.hwaccel_args(),
+            ["-hwaccel", "vaapi", "-hwaccel_output_format", "vaapi"]
+        );
+        assert_eq!(
+            VideoBackend::VideoToolbox.hwaccel_args(),
+            ["-hwaccel", "videotoolbox"]
+        );
+        assert!(VideoBackend::Cpu.hwaccel_args().is_empty());
+    }
+
+    #[test]
+    fn scale_filter_per_backend() {
+        let pad = "pad=320:180:(ow-iw)/2:(oh-ih)/2:black";
+        assert_eq!(
+            VideoBackend::Cuda.scale_filter(320, 180),
+            format!(
+                "scale_cuda=320:180:force_original_aspect_ratio=decrease:force_divisible_by=2,hwdownload,format=nv12,{}",
+                pad
+            )
+        );
+        assert_eq!(
+            VideoBackend::Vaapi.scale_filter(320, 180),
+            format!(
+                "scale_vaapi=w=320:h=180:force_original_aspect_ratio=decrease:force_divisible_by=2:format=nv12,hwdownload,format=nv12,{}",
+                pad
+            )
+        );
+        let software = format!("scale=320:180:force_original_aspect_ratio=decrease,{}", pad);
+        assert_eq!(VideoBackend::VideoToolbox.scale_filter(320, 180), software);
+        assert_eq!(VideoBackend::Cpu.scale_filter(320, 180), software);
+    }
+
+    #[test]
+    fn from_name_accepts_aliases() {
+        assert_eq!(VideoBackend::from_name("cuda"), Some(VideoBackend::Cuda));
+        assert_eq!(
+            VideoBackend::from_name(" NVIDIA "),
+            Some(VideoBackend::Cuda)
+        );
+        assert_eq!(VideoBackend::from_name("amd"), Some(VideoBackend::Vaapi));
+        assert_eq!(
+            VideoBackend::from_name("VideoToolbox"),
+            Some(VideoBackend::VideoToolbox)
+        );
+        assert_eq!(VideoBackend::from_name("none"), Some(VideoBackend::Cpu));
+        assert_eq!(VideoBackend::from_name("opencl"), None);
+        assert_eq!(VideoBackend::from_name(""), None);
+    }
+
+    #[test]
+    fn override_wins_over_detection() {
+        let all = hwaccels(&["cuda", "vaapi", "videotoolbox"]);
+        assert_eq!(
+            select_backend(Some("cpu"), true, true, &all),
+            VideoBackend::Cpu
+        );
+        assert_eq!(
+            select_backend(Some("vaapi"), false, false, &[]),
+            VideoBackend::Vaapi
+        );
+        // An unknown name falls through to detection.
+        assert_eq!(
+            select_backend(Some("bogus"), true, false, &all),
+            VideoBackend::Cuda
+        );
+    }
+
+    #[test]
+    fn detection_needs_hardware_and_ffmpeg_support() {
+        assert_eq!(
+            select_backend(None, true, false, &hwaccels(&["cuda"])),
+            VideoBackend::Cuda
+        );
+        assert_eq!(
+            select_backend(None, true, false, &hwaccels(&["vdpau"])),
+            VideoBackend::Cpu
+        );
+        assert_eq!(
+            select_backend(None, false, false, &hwaccels(&["cuda"])),
+            VideoBackend::Cpu
+        );
+        let expected = if cfg!(target_os = "linux") {
+            VideoBackend::Vaapi
+        } else {
+            VideoBackend::Cpu
+        };
+        assert_eq!(
+            select_backend(None, false, true, &hwaccels(&["vaapi"])),
+            expected
+        );
+    }
+
+    #[test]
+    fn capabilities_from_probes_apply_the_override() {
+        let caps =
+            HardwareCapabilities::from_probes(Some("vaapi"), true, false, hwaccels(&["cuda"]));
+        assert_eq!(caps.detected_backend, VideoBackend::Vaapi);
+        assert!(caps.nvidia_gpu);
+        assert!(!caps.amd_gpu);
+        assert_eq!(caps.ffmpeg_hwaccels, ["cuda"]);
+
+        let caps = HardwareCapabilities::from_probes(Some("CPU"), true, false, hwaccels(&["cuda"]));
+        assert_eq!(caps.detected_backend, VideoBackend::Cpu);
+
+        let caps = HardwareCapabilities::from_probes(None, true, false, hwaccels(&["cuda"]));
+        assert_eq!(caps.detected_backend, VideoBackend::Cuda);
+
+        let caps = HardwareCapabilities::from_probes(None, false, false, Vec::new());
+        assert_eq!(caps.detected_backend, VideoBackend::Cpu);
+    }
+
+    #[test]
+    fn parses_ffmpeg_hwaccels_listing() {
+        let stdout = "Hardware acceleration methods:\nvdpau\ncuda\n\nvaapi\n";
+        assert_eq!(parse_hwaccels(stdout), ["vdpau", "cuda", "vaapi"]);
+        assert!(parse_hwaccels("").is_empty());
+    }
+}
@@ -0,0 +1,980 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::models::jobs::{
+    BatchJob, BatchJobSpec, BatchJobSummary, ExportPlatform, ExportSettings, FileJobState,
+    FileStatus, FolderJobState, JobProgressEvent, JobStage, JobStatus, MetadataLimits,
+};
+use crate::models::metadata::EmbedMetadataRequest;
+use crate::services::ai::{self, parser, AiError};
+use crate::services::exiftool::{build_exiftool_command, execute_exiftool, get_exiftool_path};
+use crate::services::gpu_thumbnail::VideoBackend;
+use crate::services::process::CancelToken;
+use crate::services::template;
+use crate::services::thumbnail::{
+    generate_thumbnail, generate_video_thumbnail, is_video_file, OutputOptions,
+};
+
+/// Event name the UI listens on for job progress
+pub const JOB_PROGRESS_EVENT: &str = "batch-job-progress";
+
+/// Workers per folder.  The AI rate limiter decides how many of them are
+/// actually talking to the provider at once.
+const DEFAULT_JOB_CONCURRENCY: usize = 4;
+const MAX_JOB_CONCURRENCY: usize = 8;
+/// Matches `BATCH_CONFIG.MAX_THUMBNAIL_SIZE` in the grid, so the UI finds
+/// them already cached
+const JOB_THUMBNAIL_SIZE: u32 = 120;
+/// Per-file checkpoints are coalesced; status changes always write through.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(2);
+
+const MEDIA_EXTENSIONS: [&str; 12] = [
+    "jpg", "jpeg", "png", "gif", "webp", "svg", "heic", "heif", "avif", "mp4", "mov", "webm",
+];
+
+pub type EventSink = Box<dyn Fn(&JobProgressEvent) + Send + Sync>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn get_job_checkpoint_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("descify").join("jobs"))
+}
+
+/// Pause/cancel signals for one run of a job's worker
+struct JobControl {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    cancel: CancelToken,
+}
+
+impl JobControl {
+    fn new() -> Self {
+        Self {
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    fn set_paused(&self, paused: bool) {
+        *lock(&self.paused) = paused;
+        self.resumed.notify_all();
+    }
+
+    /// Blocks while the job is paused.  Returns false once it is cancelled.
+    fn wait_while_paused(&self) -> bool {
+        let mut paused = lock(&self.paused);
+        while *paused && !self.cancel.is_cancelled() {
+            paused = self
+                .resumed
+                .wait_timeout(paused, Duration::from_millis(250))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        !self.cancel.is_cancelled()
+    }
+
+    /// Sleeps for `duration`, waking early on cancellation.
+    fn sleep(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while !self.cancel.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+struct JobEntry {
+    job: Mutex<BatchJob>,
+    control: Mutex<Arc<JobControl>>,
+    /// True while a worker thread owns the job
+    worker_active: AtomicBool,
+    last_checkpoint: Mutex<Option<Instant>>,
+}
+
+impl JobEntry {
+    fn new(job: BatchJob) -> Self {
+        Self {
+            job: Mutex::new(job),
+            control: Mutex::new(Arc::new(JobControl::new())),
+            worker_active: AtomicBool::new(false),
+            last_checkpoint: Mutex::new(None),
+        }
+    }
+
+    fn control(&self) -> Arc<JobControl> {
+        Arc::clone(&lock(&self.control))
+    }
+
+    fn update<R>(&self, f: impl FnOnce(&mut BatchJob) -> R) -> R {
+        let mut job = lock(&self.job);
+        let result = f(&mut job);
+        job.updated_at = now_millis();
+        result
+    }
+
+    fn update_file(&self, folder: usize, file: usize, f: impl FnOnce(&mut FileJobState)) {
+        self.update(|job| f(&mut job.folders[folder].files[file]));
+    }
+}
+
+/// State shared between the manager and its worker threads
+struct JobContext {
+    checkpoint_dir: Option<PathBuf>,
+    sink: EventSink,
+}
+
+impl JobContext {
+    /// Writes the job to `<checkpoint dir>/<id>.json` via a temp file so a
+    /// crash mid-write never leaves a truncated checkpoint behind.
+    fn checkpoint(&self, entry: &JobEntry, force: bool) {
+        let dir = match self.checkpoint_dir {
+            Some(ref d) => d,
+            None => return,
+        };
+
+        let mut last = lock(&entry.last_checkpoint);
+        if !force && last.is_some_and(|t| t.elapsed() < CHECKPOINT_INTERVAL) {
+            return;
+        }
+        *last = Some(Instant::now());
+
+        let (id, data) = {
+            let job = lock(&entry.job);
+            (job.id.clone(), serde_json::to_vec(&*job))
+        };
+        let data = match data {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("[jobs] Failed to serialize job {}: {}", id, e);
+                return;
+            }
+        };
+
+        let path = dir.join(format!("{}.json", id));
+        let tmp = dir.join(format!("{}.json.tmp", id));
+        let result = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&tmp, &data))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(e) = result {
+            eprintln!("[jobs] Failed to checkpoint job {}: {}", id, e);
+        }
+    }
+
+    /// Emits the job's current counts, plus the state of one file if given.
+    fn emit(&self, entry: &JobEntry, folder: Option<usize>, file: Option<usize>) {
+        let event = {
+            let job = lock(&entry.job);
+            let (total_files, completed_files, failed_files) = job.counts();
+            let file_state = folder
+                .zip(file)
+                .and_then(|(fo, fi)| job.folders.get(fo).and_then(|f| f.files.get(fi)));
+            let folder_state = folder.and_then(|fo| job.folders.get(fo));
+            JobProgressEvent {
+                job_id: job.id.clone(),
+                status: job.status,
+                folder_index: folder,
+                file_path: file_state.map(|f| f.file_path.clone()),
+                stage: match file_state {
+                    Some(f) => f.stage,
+                    None => folder_state
+                        .filter(|f| f.export_path.is_some())
+                        .map(|_| JobStage::Export),
+                },
+                file_status: file_state.map(|f| f.status),
+                metadata: file_state.and_then(|f| f.metadata.clone()),
+                error: file_state
+                    .and_then(|f| f.error.clone())
+                    .or_else(|| folder_state.and_then(|f| f.error.clone()))
+                    .or_else(|| job.error.clone()),
+                export_path: folder_state.and_then(|f| f.export_path.clone()),
+                total_files,
+                completed_files,
+                failed_files,
+            }
+        };
+        (self.sink)(&event);
+    }
+}
+
+/// Owns every batch job: starts worker threads, routes pause/resume/cancel,
+/// and reloads checkpoints so interrupted jobs can be resumed after a
+/// restart or webview reload.
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, Arc<JobEntry>>>,
+    context: Arc<JobContext>,
+}
+
+impl JobManager {
+    pub fn new(sink: EventSink) -> Self {
+        let checkpoint_dir = get_job_checkpoint_dir();
+        let jobs = checkpoint_dir
+            .as_deref()
+            .map(load_checkpoints)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|job| (job.id.clone(), Arc::new(JobEntry::new(job))))
+            .collect();
+
+        Self {
+            jobs: Mutex::new(jobs),
+            context: Arc::new(JobContext {
+                checkpoint_dir,
+                sink,
+            }),
+        }
+    }
+
+    fn entry(&self, id: &str) -> Result<Arc<JobEntry>, String> {
+        lock(&self.jobs)
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Batch job not found: {}", id))
+    }
+
+    fn spawn_worker(&self, entry: Arc<JobEntry>, backend: VideoBackend) {
+        entry.worker_active.store(true, Ordering::SeqCst);
+        let context = Arc::clone(&self.context);
+        thread::spawn(move || {
+            let _guard = WorkerGuard {
+                entry: &entry,
+                context: &context,
+            };
+            run_job(&entry, &context, backend);
+        });
+    }
+
+    pub fn start(
+        &self,
+        spec: BatchJobSpec,
+        backend: VideoBackend,
+    ) -> Result<BatchJobSummary, String> {
+        if spec.folders.is_empty() {
+            return Err("No folders to process".to_string());
+        }
+        // Fail fast on missing keys/URLs instead of once per file.
+        ai::create_provider(&spec.ai).map_err(|e| e.to_string())?;
+        let templates = spec
+            .folders
+            .iter()
+            .filter_map(|f| f.template.as_deref())
+            .chain(spec.ai.prompt_template.as_deref());
+        for source in templates {
+            if let Some(e) = template::validate(source).errors.into_iter().next() {
+                return Err(e.to_string());
+            }
+        }
+
+        let folders = spec
+            .folders
+            .iter()
+            .map(|f| FolderJobState {
+                folder_path: f.folder_path.clone(),
+                folder_name: Path::new(&f.folder_path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| f.folder_path.clone()),
+                prompt: f.prompt.clone(),
+                template: f.template.clone(),
+                scanned: !f.files.is_empty(),
+                files: f.files.iter().cloned().map(FileJobState::new).collect(),
+                export_path: None,
+                error: None,
+            })
+            .collect();
+
+        let now = now_millis();
+        let job = BatchJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            status: JobStatus::Queued,
+            spec,
+            folders,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let summary = job.summary();
+
+        let entry = Arc::new(JobEntry::new(job));
+        lock(&self.jobs).insert(summary.id.clone(), Arc::clone(&entry));
+        self.context.checkpoint(&entry, true);
+        self.spawn_worker(entry, backend);
+        Ok(summary)
+    }
+
+    pub fn pause(&self, id: &str) -> Result<BatchJobSummary, String> {
+        let entry = self.entry(id)?;
+        let status = lock(&entry.job).status;
+        if !matches!(status, JobStatus::Queued | JobStatus::Running) {
+            return Err(format!("Job is not running ({:?})", status));
+        }
+
+        entry.control().set_paused(true);
+        let summary = entry.update(|job| {
+            job.status = JobStatus::Paused;
+            job.summary()
+        });
+        self.context.checkpoint(&entry, true);
+        self.context.emit(&entry, None, None);
+        Ok(summary)
+    }
+
+    /// Continue a paused job, or restart the worker for one that was
+    /// interrupted, cancelled or finished with failures.  Files that already
+    /// completed are skipped; failed ones are retried.
+    pub fn resume(&self, id: &str, backend: VideoBackend) -> Result<BatchJobSummary, String> {
+        let entry = self.entry(id)?;
+        // The worker clears `worker_active` under the job lock as it writes
+        // the final status, so holding the lock here means the flag and the
+        // status agree: a live worker will see the un-pause, a finished one
+        // has already settled the job.
+        let mut job = lock(&entry.job);
+
+        if entry.worker_active.load(Ordering::SeqCst) {
+            let control = entry.control();
+            if control.cancel.is_cancelled() {
+                return Err("Job is being cancelled".to_string());
+            }
+            control.set_paused(false);
+            job.status = JobStatus::Running;
+            job.updated_at = now_millis();
+            let summary = job.summary();
+            drop(job);
+            self.context.checkpoint(&entry, true);
+            self.context.emit(&entry, None, None);
+            return Ok(summary);
+        }
+
+        // Keys may have been removed since the job was checkpointed.
+        ai::create_provider(&job.spec.ai).map_err(|e| e.to_string())?;
+
+        job.status = JobStatus::Queued;
+        job.error = None;
+        job.updated_at = now_millis();
+        let summary = job.summary();
+        *lock(&entry.control) = Arc::new(JobControl::new());
+        // Claim the job before letting go of the lock so a second resume
+        // can't start another worker.
+        entry.worker_active.store(true, Ordering::SeqCst);
+        drop(job);
+
+        self.spawn_worker(entry, backend);
+        Ok(summary)
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<BatchJobSummary, String> {
+        let entry = self.entry(id)?;
+        let control = entry.control();
+        control.cancel.cancel();
+        // Wake paused workers so they can observe the cancellation.
+        control.set_paused(false);
+
+        let summary = entry.update(|job| {
+            if !job.status.is_finished() {
+                job.status = JobStatus::Cancelled;
+            }
+            job.summary()
+        });
+        self.context.checkpoint(&entry, true);
+        self.context.emit(&entry, None, None);
+        Ok(summary)
+    }
+
+    pub fn get(&self, id: &str) -> Result<BatchJob, String> {
+        Ok(lock(&self.entry(id)?.job).clone())
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<BatchJobSummary> {
+        let mut summaries: Vec<BatchJobSummary> = lock(&self.jobs)
+            .values()
+            .map(|e| lock(&e.job).summary())
+            .collect();
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+        summaries
+    }
+
+    /// Forget a job that is not running and delete its checkpoint.
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        {
+            // Same lock order as `list`: the map, then the job.
+            let mut jobs = lock(&self.jobs);
+            let entry = jobs
+                .get(id)
+                .cloned()
+                .ok_or_else(|| format!("Batch job not found: {}", id))?;
+            // Checked under the job lock like `resume`, so a resume can't
+            // claim the job between the check and the removal.
+            let _job = lock(&entry.job);
+            if entry.worker_active.load(Ordering::SeqCst) {
+                return Err("Cancel the job before deleting it".to_string());
+            }
+            jobs.remove(id);
+        }
+        if let Some(ref dir) = self.context.checkpoint_dir {
+            let path = dir.join(format!("{}.json", id));
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to delete {:?}: {}", path, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads every checkpoint in `dir`.  Jobs that were running when the app
+/// went away come back paused, with in-flight files reset to pending.
+fn load_checkpoints(dir: &Path) -> Vec<BatchJob> {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| {
+            let data = fs::read(&p).ok()?;
+            match serde_json::from_slice::<BatchJob>(&data) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    eprintln!("[jobs] Ignoring unreadable checkpoint {:?}: {}", p, e);
+                    None
+                }
+            }
+        })
+        .map(|mut job| {
+            if matches!(job.status, JobStatus::Queued | JobStatus::Running) {
+                job.status = JobStatus::Paused;
+            }
+            for file in job.folders.iter_mut().flat_map(|f| f.files.iter_mut()) {
+                if file.status == FileStatus::Processing {
+                    file.status = FileStatus::Pending;
+                    file.stage = None;
+                }
+            }
+            job
+        })
+        .collect()
+}
+
+fn is_media_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.as_str()))
+}
+
+fn scan_folder(folder: &str) -> Result<Vec<String>, String> {
+    let mut files: Vec<String> = fs::read_dir(folder)
+        .map_err(|e| format!("Failed to read folder {}: {}", folder, e))?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && is_media_file(p))
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Fails the job if its worker unwinds before `run_job` settles it, so a
+/// panic doesn't leave the job claimed and unresumable.
+struct WorkerGuard<'a> {
+    entry: &'a JobEntry,
+    context: &'a JobContext,
+}
+
+impl Drop for WorkerGuard<'_> {
+    fn drop(&mut self) {
+        let mut job = lock(&self.entry.job);
+        if !self.entry.worker_active.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        job.status = JobStatus::Failed;
+        job.error = Some("The job's worker stopped unexpectedly".to_string());
+        job.updated_at = now_millis();
+        drop(job);
+        self.context.checkpoint(self.entry, true);
+        self.context.emit(self.entry, None, None);
+    }
+}
+
+fn run_job(entry: &Arc<JobEntry>, context: &JobContext, backend: VideoBackend) {
+    let control = entry.control();
+    let folder_count = entry.update(|job| {
+        if job.status == JobStatus::Queued {
+            job.status = JobStatus::Running;
+        }
+        job.folders.len()
+    });
+    context.checkpoint(entry, true);
+    context.emit(entry, None, None);
+
+    for folder in 0..folder_count {
+        if !control.wait_while_paused() {
+            break;
+        }
+
+        let (folder_path, scanned) = {
+            let job = lock(&entry.job);
+            let state = &job.folders[folder];
+            (state.folder_path.clone(), state.scanned)
+        };
+        if !scanned {
+            match scan_folder(&folder_path) {
+                Ok(files) => entry.update(|job| {
+                    let state = &mut job.folders[folder];
+                    state.files = files.into_iter().map(FileJobState::new).collect();
+                    state.scanned = true;
+                }),
+                Err(e) => {
+                    entry.update(|job| job.folders[folder].error = Some(e));
+                    context.emit(entry, Some(folder), None);
+                    continue;
+                }
+            }
+        }
+
+        process_folder(entry, context, &control, folder, backend);
+        if control.cancel.is_cancelled() {
+            break;
+        }
+        export_folder(entry, context, folder);
+    }
+
+    entry.update(|job| {
+        job.status = if control.cancel.is_cancelled() {
+            JobStatus::Cancelled
+        } else {
+            JobStatus::Completed
+        };
+        // Released under the job lock, see `resume`.
+        entry.worker_active.store(false, Ordering::SeqCst);
+    });
+    context.checkpoint(entry, true);
+    context.emit(entry, None, None);
+}
+
+fn process_folder(
+    entry: &JobEntry,
+    context: &JobContext,
+    control: &JobControl,
+    folder: usize,
+    backend: VideoBackend,
+) {
+    let (file_count, workers, delay) = {
+        let job = lock(&entry.job);
+        let count = job.folders[folder].files.len();
+        let workers = job
+            .spec
+            .concurrency
+            .unwrap_or(DEFAULT_JOB_CONCURRENCY)
+            .clamp(1, MAX_JOB_CONCURRENCY)
+            .min(count.max(1));
+        (
+            count,
+            workers,
+            Duration::from_millis(job.spec.request_delay_ms),
+        )
+    };
+
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if !control.wait_while_paused() {
+                    return;
+                }
+                let file = next.fetch_add(1, Ordering::SeqCst);
+                if file >= file_count {
+                    return;
+                }
+
+                let status = lock(&entry.job).folders[folder].files[file].status;
+                if matches!(status, FileStatus::Completed | FileStatus::Skipped) {
+                    continue;
+                }
+
+                process_file(entry, context, control, folder, file, backend);
+                if !delay.is_zero() {
+                    control.sleep(delay);
+                }
+            });
+        }
+    });
+}
+
+fn fail_file(
+    entry: &JobEntry,
+    context: &JobContext,
+    folder: usize,
+    file: usize,
+    stage: JobStage,
+    error: String,
+) {
+    entry.update_file(folder, file, |f| {
+        f.status = FileStatus::Failed;
+        f.stage = Some(stage);
+        f.error = Some(error);
+    });
+    context.checkpoint(entry, false);
+    context.emit(entry, Some(folder), Some(file));
+}
+
+fn set_stage(entry: &JobEntry, context: &JobContext, folder: usize, file: usize, stage: JobStage) {
+    entry.update_file(folder, file, |f| f.stage = Some(stage));
+    context.emit(entry, Some(folder), Some(file));
+}
+
+fn process_file(
+    entry: &JobEntry,
+    context: &JobContext,
+    control: &JobControl,
+    folder: usize,
+    file: usize,
+    backend: VideoBackend,
+) {
+    let (job_id, file_path, spec, prompt, prompt_template, existing, embedded) = {
+        let mut job = lock(&entry.job);
+        let job_id = job.id.clone();
+        let spec = job.spec.clone();
+        let state = &mut job.folders[folder];
+        let prompt = state.prompt.clone();
+        let prompt_template = state.template.clone();
+        let f = &mut state.files[file];
+        f.status = FileStatus::Processing;
+        f.stage = Some(JobStage::Thumbnail);
+        f.error = None;
+        (
+            job_id,
+            f.file_path.clone(),
+            spec,
+            prompt,
+            prompt_template,
+            f.metadata.clone(),
+            f.embedded,
+        )
+    };
+    context.emit(entry, Some(folder), Some(file));
+
+    let path = Path::new(&file_path);
+    if !path.is_file() {
+        return fail_file(
+            entry,
+            context,
+            folder,
+            file,
+            JobStage::Thumbnail,
+            format!("File does not exist: {}", file_path),
+        );
+    }
+
+    // Warms the thumbnail cache for the grid; a failure here only means the
+    // UI falls back to generating its own.
+    if spec.generate_thumbnails {
+        if is_video_file(path) {
+            generate_video_thumbnail(&file_path, JOB_THUMBNAIL_SIZE, backend);
+        } else {
+            generate_thumbnail(&file_path, JOB_THUMBNAIL_SIZE, &OutputOptions::default());
+        }
+    }
+
+    // Metadata survives a failed embed, so a retry doesn't re-bill the API.
+    let metadata = match existing {
+        Some(m) => m,
+        None => {
+            set_stage(entry, context, folder, file, JobStage::Generate);
+            let mut options = spec.ai.clone();
+            if let Some(template) = prompt_template {
+                options.prompt_template = Some(template);
+            } else if let Some(prompt) = prompt {
+                options.prompt = prompt;
+                options.prompt_template = None;
+            }
+            options.keyword_limit = Some(spec.limits.keyword_limit);
+            options.title_limit = Some(spec.limits.title_limit);
+            options.description_limit = Some(spec.limits.description_limit);
+            let result = ai::generate_metadata(
+                &file_path,
+                &options,
+                backend,
+                Some(&job_id),
+                Some(&control.cancel),
+            );
+            match result {
+                Ok(result) => {
+                    let metadata = result.metadata;
+                    entry.update_file(folder, file, |f| {
+                        f.metadata = Some(metadata.clone());
+                        f.cached = result.cached;
+                    });
+                    metadata
+                }
+                // Never sent, so it goes back in the queue for a resume.
+                Err(AiError::Cancelled) => {
+                    entry.update_file(folder, file, |f| {
+                        f.status = FileStatus::Pending;
+                        f.stage = None;
+                    });
+                    context.checkpoint(entry, false);
+                    return context.emit(entry, Some(folder), Some(file));
+                }
+                Err(e) => {
+                    return fail_file(
+                        entry,
+                        context,
+                        folder,
+                        file,
+                        JobStage::Generate,
+                        e.to_string(),
+                    )
+                }
+            }
+        }
+    };
+
+    set_stage(entry, context, folder, file, JobStage::Validate);
+    let warnings = parser::validate_metadata(&metadata, &spec.limits);
+    entry.update_file(folder, file, |f| f.warnings = warnings);
+
+    let is_svg = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+    if let Some(fields) = spec.embed.filter(|_| !embedded && !is_svg) {
+        set_stage(entry, context, folder, file, JobStage::Embed);
+        let request = EmbedMetadataRequest {
+            file_path: file_path.clone(),
+            title: fields.title.then(|| metadata.title.clone()),
+            description: fields.description.then(|| metadata.description.clone()),
+            keywords: fields.keywords.then(|| metadata.keywords.join(", ")),
+        };
+        let exiftool_path = get_exiftool_path();
+        let cmd = build_exiftool_command(&exiftool_path, &request);
+        match execute_exiftool(cmd, &request, &exiftool_path, Some(control.cancel.clone())) {
+            Ok(result) if result.success => {
+                entry.update_file(folder, file, |f| f.embedded = true);
+            }
+            Ok(result) => {
+                return fail_file(
+                    entry,
+                    context,
+                    folder,
+                    file,
+                    JobStage::Embed,
+                    result.message,
+                )
+            }
+            Err(e) => return fail_file(entry, context, folder, file, JobStage::Embed, e),
+        }
+    }
+
+    entry.update_file(folder, file, |f| {
+        f.status = FileStatus::Completed;
+        f.stage = None;
+    });
+    context.checkpoint(entry, false);
+    context.emit(entry, Some(folder), Some(file));
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Builds the Adobe Stock or Shutterstock upload CSV for the completed
+/// files of one folder.
+fn build_export_csv(folder: &FolderJobState, settings: &ExportSettings) -> Option<String> {
+    let (headers, categories): (&[&str], Vec<&str>) = match settings.platform {
+        ExportPlatform::AdobeStock => (
+            &["Filename", "Title", "Description", "Keywords", "Category"],
+            vec![settings.adobe_category.as_deref().unwrap_or("")],
+        ),
+        ExportPlatform::Shutterstock => (
+            &[
+                "Filename",
+                "Title",
+                "Description",
+                "Keywords",
+                "Category 1",
+                "Category 2",
+            ],
+            vec![
+                settings.shutterstock_category1.as_deref().unwrap_or(""),
+                settings.shutterstock_category2.as_deref().unwrap_or(""),
+            ],
+        ),
+    };
+
+    let mut rows = vec![headers.join(",")];
+    for file in &folder.files {
+        let metadata = match (file.status, &file.metadata) {
+            (FileStatus::Completed, Some(m)) => m,
+            _ => continue,
+        };
+        let file_name = Path::new(&file.file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut row = vec![
+            escape_csv_field(&file_name),
+            escape_csv_field(&metadata.title),
+            escape_csv_field(&metadata.description),
+            escape_csv_field(&metadata.keywords.join(", ")),
+        ];
+        row.extend(categories.iter().map(|c| escape_csv_field(c)));
+        rows.push(row.join(","));
+    }
+
+    (rows.len() > 1).then(|| rows.join("\n"))
+}
+
+fn export_folder(entry: &JobEntry, context: &JobContext, folder: usize) {
+    let (settings, state) = {
+        let job = lock(&entry.job);
+        match job.spec.export {
+            Some(ref s) => (s.clone(), job.folders[folder].clone()),
+            None => return,
+        }
+    };
+
+    let csv = match build_export_csv(&state, &settings) {
+        Some(c) => c,
+        None => return,
+    };
+
+    let platform_name = match settings.platform {
+        ExportPlatform::AdobeStock => "Adobe_Stock",
+        ExportPlatform::Shutterstock => "shutterstock",
+    };
+    let file_name = format!(
+        "{}_{}_Export_{}.csv",
+        state.folder_name,
+        platform_name,
+        chrono::Local::now().format("%Y-%m-%d")
+    );
+    let path = Path::new(&settings.directory).join(file_name);
+
+    let result = fs::create_dir_all(&settings.directory)
+        .and_then(|_| fs::write(&path, csv))
+        .map_err(|e| format!("Export failed for {}: {}", state.folder_name, e));
+    entry.update(|job| {
+        let state = &mut job.folders[folder];
+        match result {
+            Ok(()) => {
+                state.export_path = Some(path.to_string_lossy().into_owned());
+                state.error = None;
+            }
+            Err(e) => state.error = Some(e),
+        }
+    });
+    context.checkpoint(entry, true);
+    context.emit(entry, Some(folder), None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_entry(status: &str) -> Arc<JobEntry> {
+        let job: BatchJob = serde_json::from_value(serde_json::json!({
+            "id": "job-1",
+            "status": status,
+            "spec": { "folders": [], "ai": { "provider": "local" } },
+            "folders": [],
+            "error": null,
+            "created_at": 0,
+            "updated_at": 0,
+        }))
+        .unwrap();
+        Arc::new(JobEntry::new(job))
+    }
+
+    fn test_manager(entry: &Arc<JobEntry>) -> (JobManager, Arc<AtomicUsize>) {
+        let events = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&events);
+        let manager = JobManager {
+            jobs: Mutex::new(HashMap::from([("job-1".to_string(), Arc::clone(entry))])),
+            context: Arc::new(JobContext {
+                checkpoint_dir: None,
+                sink: Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }),
+            }),
+        };
+        (manager, events)
+    }
+
+    #[test]
+    fn panicking_worker_releases_and_fails_the_job() {
+        let entry = test_entry("running");
+        let (manager, events) = test_manager(&entry);
+        entry.worker_active.store(true, Ordering::SeqCst);
+
+        let context = Arc::clone(&manager.context);
+        let worker = {
+            let entry = Arc::clone(&entry);
+            thread::spawn(move || {
+                let _guard = WorkerGuard {
+                    entry: &entry,
+                    context: &context,
+                };
+                panic!("worker blew up");
+            })
+        };
+        assert!(worker.join().is_err());
+
+        let job = manager.get("job-1").unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.is_some());
+        assert!(!entry.worker_active.load(Ordering::SeqCst));
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+        manager.delete("job-1").unwrap();
+    }
+
+    #[test]
+    fn finished_worker_keeps_its_status() {
+        let entry = test_entry("completed");
+        let (manager, events) = test_manager(&entry);
+        drop(WorkerGuard {
+            entry: &entry,
+            context: &manager.context,
+        });
+        assert_eq!(manager.get("job-1").unwrap().status, JobStatus::Completed);
+        assert_eq!(events.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn delete_refuses_a_claimed_job() {
+        let entry = test_entry("running");
+        let (manager, _) = test_manager(&entry);
+        entry.worker_active.store(true, Ordering::SeqCst);
+        assert!(manager.delete("job-1").is_err());
+        assert!(manager.get("job-1").is_ok());
+
+        entry.worker_active.store(false, Ordering::SeqCst);
+        manager.delete("job-1").unwrap();
+        assert!(manager.get("job-1").is_err());
+    }
+}
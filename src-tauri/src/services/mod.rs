@@ -1,3 +1,10 @@
+pub mod ai;
 pub mod exiftool;
 pub mod gpu_thumbnail;
+pub mod jobs;
+pub mod process;
+pub mod secrets;
+pub mod template;
 pub mod thumbnail;
+pub mod tools;
+pub mod video_probe;
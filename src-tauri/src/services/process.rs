@@ -0,0 +1,468 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the runner checks for exit, timeout and cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Shared flag that asks running processes to stop.  Clones share state,
+/// so one token can cancel every process started with it.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Limits applied to a single process run
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub timeout: Duration,
+    /// Stdout beyond this is treated as an error, since callers parse it
+    pub max_stdout_bytes: usize,
+    /// Only the last `max_stderr_bytes` of stderr are kept
+    pub max_stderr_bytes: usize,
+    pub cancel: Option<CancelToken>,
+}
+
+impl RunOptions {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            max_stdout_bytes: 64 * 1024 * 1024,
+            max_stderr_bytes: 64 * 1024,
+            cancel: None,
+        }
+    }
+
+    pub fn max_stdout(mut self, bytes: usize) -> Self {
+        self.max_stdout_bytes = bytes;
+        self
+    }
+
+    pub fn cancel_token(mut self, token: Option<CancelToken>) -> Self {
+        self.cancel = token;
+        self
+    }
+}
+
+/// Captured result of a process that ran to completion
+#[derive(Debug)]
+pub struct ProcessOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ProcessOutput {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    pub fn stderr_text(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).trim().to_string()
+    }
+
+    /// Turns a non-zero exit into [`ProcessError::Failed`].
+    pub fn check(self, program: &str) -> Result<ProcessOutput, ProcessError> {
+        if self.success() {
+            Ok(self)
+        } else {
+            Err(ProcessError::Failed {
+                program: program.to_string(),
+                code: self.status.code(),
+                stderr: self.stderr_text(),
+            })
+        }
+    }
+}
+
+/// Why a process run did not produce output
+#[derive(Debug)]
+pub enum ProcessError {
+    /// The binary does not exist at the given path
+    NotFound {
+        program: String,
+    },
+    Spawn {
+        program: String,
+        error: io::Error,
+    },
+    TimedOut {
+        program: String,
+        timeout: Duration,
+    },
+    Cancelled {
+        program: String,
+    },
+    OutputTooLarge {
+        program: String,
+        limit: usize,
+    },
+    /// The process exited with a non-zero status
+    Failed {
+        program: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    Io {
+        program: String,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::NotFound { program } => write!(f, "{} not found", program),
+            ProcessError::Spawn { program, error } => {
+                write!(f, "Failed to execute {}: {}", program, error)
+            }
+            ProcessError::TimedOut { program, timeout } => {
+                write!(f, "{} timed out after {:?}", program, timeout)
+            }
+            ProcessError::Cancelled { program } => write!(f, "{} was cancelled", program),
+            ProcessError::OutputTooLarge { program, limit } => {
+                write!(
+                    f,
+                    "{} produced more than {} bytes of output",
+                    program, limit
+                )
+            }
+            ProcessError::Failed {
+                program,
+                code,
+                stderr,
+            } => write!(
+                f,
+                "{} failed with exit code {}: {}",
+                program,
+                code.unwrap_or(-1),
+                stderr
+            ),
+            ProcessError::Io { program, error } => {
+                write!(f, "I/O error talking to {}: {}", program, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// Reads `reader` to the end on a background thread, keeping at most
+/// `limit` bytes.  With `overflow` set the reader gives up and raises the
+/// flag once the limit is passed; otherwise it keeps draining (so the child
+/// never blocks on a full pipe) and retains only the last `limit` bytes.
+fn spawn_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    limit: usize,
+    overflow: Option<Arc<AtomicBool>>,
+) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            if data.len() > limit {
+                match overflow {
+                    Some(ref flag) => {
+                        flag.store(true, Ordering::SeqCst);
+                        data.clear();
+                        break;
+                    }
+                    None => {
+                        let excess = data.len() - limit;
+                        data.drain(..excess);
+                    }
+                }
+            }
+        }
+        Ok(data)
+    })
+}
+
+/// Kills every process left in the group led by `pid`, including ones
+/// whose leader has already exited.  Windows has no equivalent once the
+/// leader is gone.
+fn kill_process_group(pid: u32) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill() has no memory-safety preconditions; a negative pid
+        // targets the process group created in `run`.
+        unsafe {
+            libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Kills `child` and everything it started.  On Unix the child leads its
+/// own process group, so the whole group is signalled.
+fn kill_process_tree(child: &mut Child) {
+    kill_process_group(child.id());
+
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &child.id().to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Display name for error messages: the file name of the program.
+fn program_name(cmd: &Command) -> String {
+    let program = PathBuf::from(cmd.get_program());
+    program
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| program.to_string_lossy().into_owned())
+}
+
+/// Runs `cmd` to completion under the limits in `options`.
+///
+/// Stdin is closed.  On timeout, cancellation or oversized stdout the
+/// process tree is killed and an error is returned; a non-zero exit is not
+/// an error here (see [`ProcessOutput::check`]).
+pub fn run(mut cmd: Command, options: &RunOptions) -> Result<ProcessOutput, ProcessError> {
+    let program = program_name(&cmd);
+
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    let mut child = cmd.spawn().map_err(|error| {
+        if error.kind() == io::ErrorKind::NotFound {
+            ProcessError::NotFound {
+                program: program.clone(),
+            }
+        } else {
+            ProcessError::Spawn {
+                program: program.clone(),
+                error,
+            }
+        }
+    })?;
+
+    let stdout_overflow = Arc::new(AtomicBool::new(false));
+    let stdout = child.stdout.take().map(|s| {
+        spawn_reader(
+            s,
+            options.max_stdout_bytes,
+            Some(Arc::clone(&stdout_overflow)),
+        )
+    });
+    let stderr = child
+        .stderr
+        .take()
+        .map(|s| spawn_reader(s, options.max_stderr_bytes, None));
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(error) => {
+                kill_process_tree(&mut child);
+                return Err(ProcessError::Io { program, error });
+            }
+        }
+
+        if options.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            kill_process_tree(&mut child);
+            return Err(ProcessError::Cancelled { program });
+        }
+        if started.elapsed() >= options.timeout {
+            kill_process_tree(&mut child);
+            return Err(ProcessError::TimedOut {
+                program,
+                timeout: options.timeout,
+            });
+        }
+        if stdout_overflow.load(Ordering::SeqCst) {
+            kill_process_tree(&mut child);
+            return Err(ProcessError::OutputTooLarge {
+                program,
+                limit: options.max_stdout_bytes,
+            });
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    // A grandchild that inherited the pipes keeps them open after the child
+    // exits, so the readers get the same deadline.  Killing the group closes
+    // the pipes; the detached readers then finish on their own.
+    let pid = child.id();
+    let finished = |h: &Option<thread::JoinHandle<io::Result<Vec<u8>>>>| {
+        h.as_ref().is_none_or(|h| h.is_finished())
+    };
+    while !(finished(&stdout) && finished(&stderr)) {
+        if options.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            kill_process_group(pid);
+            return Err(ProcessError::Cancelled { program });
+        }
+        if started.elapsed() >= options.timeout {
+            kill_process_group(pid);
+            return Err(ProcessError::TimedOut {
+                program,
+                timeout: options.timeout,
+            });
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    let join = |handle: Option<thread::JoinHandle<io::Result<Vec<u8>>>>| {
+        handle
+            .map(|h| h.join().unwrap_or_else(|_| Ok(Vec::new())))
+            .unwrap_or_else(|| Ok(Vec::new()))
+    };
+
+    let stdout = join(stdout).map_err(|error| ProcessError::Io {
+        program: program.clone(),
+        error,
+    })?;
+    let stderr = join(stderr).map_err(|error| ProcessError::Io {
+        program: program.clone(),
+        error,
+    })?;
+
+    // The process can exit between the last poll and the reader noticing.
+    if stdout_overflow.load(Ordering::SeqCst) {
+        return Err(ProcessError::OutputTooLarge {
+            program,
+            limit: options.max_stdout_bytes,
+        });
+    }
+
+    Ok(ProcessOutput {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        cmd
+    }
+
+    #[test]
+    fn captures_output_and_status() {
+        let output = run(
+            sh("echo out; echo err >&2; exit 3"),
+            &RunOptions::with_timeout(Duration::from_secs(10)),
+        )
+        .unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr_text(), "err");
+        assert_eq!(output.status.code(), Some(3));
+        assert!(matches!(
+            output.check("sh"),
+            Err(ProcessError::Failed { code: Some(3), .. })
+        ));
+    }
+
+    #[test]
+    fn kills_a_process_that_runs_past_the_timeout() {
+        let started = Instant::now();
+        let result = run(
+            sh("sleep 10"),
+            &RunOptions::with_timeout(Duration::from_millis(200)),
+        );
+        assert!(matches!(result, Err(ProcessError::TimedOut { .. })));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn grandchild_holding_the_pipes_does_not_outlive_the_timeout() {
+        // The shell exits at once; the backgrounded sleep keeps stdout open.
+        let started = Instant::now();
+        let result = run(
+            sh("sleep 10 & echo started"),
+            &RunOptions::with_timeout(Duration::from_millis(300)),
+        );
+        assert!(matches!(result, Err(ProcessError::TimedOut { .. })));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cancel_token_stops_the_process() {
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+        let started = Instant::now();
+        let result = run(
+            sh("sleep 10"),
+            &RunOptions::with_timeout(Duration::from_secs(30)).cancel_token(Some(token)),
+        );
+        assert!(matches!(result, Err(ProcessError::Cancelled { .. })));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn stdout_over_the_cap_is_an_error() {
+        let result = run(
+            sh("head -c 100000 /dev/zero"),
+            &RunOptions::with_timeout(Duration::from_secs(10)).max_stdout(1000),
+        );
+        match result {
+            Err(ProcessError::OutputTooLarge { limit, .. }) => assert_eq!(limit, 1000),
+            other => panic!("expected OutputTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stderr_keeps_only_the_tail() {
+        let mut options = RunOptions::with_timeout(Duration::from_secs(10));
+        options.max_stderr_bytes = 4;
+        let output = run(sh("printf 0123456789 >&2"), &options).unwrap();
+        assert_eq!(output.stderr, b"6789");
+    }
+
+    #[test]
+    fn missing_binary_is_not_found() {
+        let result = run(
+            Command::new("/nonexistent/descify-test-binary"),
+            &RunOptions::with_timeout(Duration::from_secs(1)),
+        );
+        assert!(matches!(result, Err(ProcessError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,367 @@
+// Provider API keys, kept on the backend so they never have to round-trip
+// through the webview once saved.  Keys live in the platform keyring (Secret
+// Service on Linux, Keychain on macOS, Credential Manager on Windows).  Linux
+// desktops without a keyring daemon fall back to an AES-GCM encrypted file in
+// the config directory, keyed to the machine and user.
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::services::ai::rate_limit::key_id;
+use crate::services::ai::ProviderKind;
+
+const KEYRING_SERVICE: &str = "descify";
+const FALLBACK_FILE_VERSION: u32 = 1;
+const FALLBACK_KDF_CONTEXT: &str = "descify 2025-01-01 api key fallback file";
+
+lazy_static! {
+    static ref BACKEND: SecretBackend = detect_backend();
+    /// Decrypted keys per provider, filled on first read so batch jobs don't
+    /// hit D-Bus for every file.  Also serialises read-modify-write cycles.
+    static ref CACHE: Mutex<HashMap<ProviderKind, Vec<String>>> = Mutex::new(HashMap::new());
+}
+
+/// Where stored keys end up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    Keyring,
+    EncryptedFile,
+}
+
+/// What the UI gets to see about a stored key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredKey {
+    pub provider: ProviderKind,
+    /// Same id the key pool and rate limiter report
+    pub key_id: String,
+    /// Last few characters, enough to tell keys apart
+    pub hint: String,
+    pub backend: SecretBackend,
+}
+
+/// On-disk layout of the fallback file
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn lock_cache() -> MutexGuard<'static, HashMap<ProviderKind, Vec<String>>> {
+    CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn keyring_entry(provider: ProviderKind) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, &provider.to_string())
+        .map_err(|e| format!("Failed to open keyring entry: {}", e))
+}
+
+/// A missing entry means the keyring works; anything else (no D-Bus session,
+/// no Secret Service provider) means it doesn't.
+fn detect_backend() -> SecretBackend {
+    let probe = keyring::Entry::new(KEYRING_SERVICE, "availability-check")
+        .and_then(|entry| entry.get_password());
+    match probe {
+        Ok(_) | Err(keyring::Error::NoEntry) => SecretBackend::Keyring,
+        Err(e) => {
+            eprintln!(
+                "[secrets] System keyring unavailable ({}), using encrypted file",
+                e
+            );
+            SecretBackend::EncryptedFile
+        }
+    }
+}
+
+pub fn backend() -> SecretBackend {
+    *BACKEND
+}
+
+fn read_keyring(provider: ProviderKind) -> Result<Vec<String>, String> {
+    match keyring_entry(provider)?.get_password() {
+        Ok(data) => serde_json::from_str(&data)
+            .map_err(|e| format!("Stored keys for {} are corrupt: {}", provider, e)),
+        Err(keyring::Error::NoEntry) => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read keys for {}: {}", provider, e)),
+    }
+}
+
+fn write_keyring(provider: ProviderKind, keys: &[String]) -> Result<(), String> {
+    let entry = keyring_entry(provider)?;
+    if keys.is_empty() {
+        return match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete keys for {}: {}", provider, e)),
+        };
+    }
+    let data = serde_json::to_string(keys)
+        .map_err(|e| format!("Failed to serialize keys for {}: {}", provider, e))?;
+    entry
+        .set_password(&data)
+        .map_err(|e| format!("Failed to save keys for {}: {}", provider, e))
+}
+
+fn get_fallback_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("descify").join("secrets.json"))
+}
+
+/// Machine id and user name are mixed into the file key, so a copied or
+/// synced config directory can't be decrypted elsewhere.  This keeps keys
+/// out of backups and dotfile repos; it is no defence against code running
+/// as the same user, which could read the keyring just as easily.
+fn fallback_key(salt: &[u8]) -> [u8; 32] {
+    let machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok())
+        .unwrap_or_default();
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    let home = dirs::home_dir().unwrap_or_default();
+
+    let mut material = Vec::new();
+    material.extend_from_slice(machine_id.trim().as_bytes());
+    material.push(0);
+    material.extend_from_slice(user.as_bytes());
+    material.push(0);
+    material.extend_from_slice(home.to_string_lossy().as_bytes());
+    material.push(0);
+    material.extend_from_slice(salt);
+    blake3::derive_key(FALLBACK_KDF_CONTEXT, &material)
+}
+
+fn read_fallback_file() -> Result<HashMap<ProviderKind, Vec<String>>, String> {
+    let Some(path) = get_fallback_path().filter(|p| p.exists()) else {
+        return Ok(HashMap::new());
+    };
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let file: EncryptedFile =
+        serde_json::from_slice(&data).map_err(|e| format!("{:?} is corrupt: {}", path, e))?;
+    if file.version != FALLBACK_FILE_VERSION {
+        return Err(format!(
+            "{:?} has unsupported version {}",
+            path, file.version
+        ));
+    }
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let decode = |field: &str| {
+        b64.decode(field)
+            .map_err(|e| format!("{:?} is corrupt: {}", path, e))
+    };
+    let salt = decode(&file.salt)?;
+    let nonce = decode(&file.nonce)?;
+    let ciphertext = decode(&file.ciphertext)?;
+    if nonce.len() != 12 {
+        return Err(format!("{:?} is corrupt: bad nonce length", path));
+    }
+
+    let key = fallback_key(&salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| {
+            format!(
+                "Could not decrypt {:?}; it was written on another machine or by another user",
+                path
+            )
+        })?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("{:?} is corrupt: {}", path, e))
+}
+
+fn write_fallback_file(keys: &HashMap<ProviderKind, Vec<String>>) -> Result<(), String> {
+    let path = get_fallback_path()
+        .ok_or_else(|| "Could not determine the config directory".to_string())?;
+    let keys: HashMap<_, _> = keys.iter().filter(|(_, v)| !v.is_empty()).collect();
+    if keys.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to delete {:?}: {}", path, e))
+            }
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+
+    let plaintext =
+        serde_json::to_vec(&keys).map_err(|e| format!("Failed to serialize keys: {}", e))?;
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = fallback_key(&salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| "Failed to encrypt keys".to_string())?;
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let file = EncryptedFile {
+        version: FALLBACK_FILE_VERSION,
+        salt: b64.encode(salt),
+        nonce: b64.encode(nonce),
+        ciphertext: b64.encode(ciphertext),
+    };
+    let data =
+        serde_json::to_vec_pretty(&file).map_err(|e| format!("Failed to serialize keys: {}", e))?;
+
+    let tmp = path.with_extension("json.tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&tmp)
+        .and_then(|mut f| f.write_all(&data))
+        .and_then(|_| fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Keys from the active backend, plus any left in the fallback file from
+/// sessions where the keyring daemon wasn't running.
+fn load(provider: ProviderKind) -> Result<Vec<String>, String> {
+    let (mut keys, mut file) = match backend() {
+        SecretBackend::Keyring => {
+            // A leftover file that no longer decrypts must not hide the
+            // keyring's keys.
+            let file = read_fallback_file().unwrap_or_else(|e| {
+                eprintln!("[secrets] Ignoring fallback file: {}", e);
+                HashMap::new()
+            });
+            (read_keyring(provider)?, file)
+        }
+        SecretBackend::EncryptedFile => (Vec::new(), read_fallback_file()?),
+    };
+    for key in file.remove(&provider).unwrap_or_default() {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+fn save(provider: ProviderKind, keys: &[String]) -> Result<(), String> {
+    match backend() {
+        SecretBackend::Keyring => {
+            write_keyring(provider, keys)?;
+            // Anything stranded in the fallback file now lives in the keyring.
+            if let Ok(mut file) = read_fallback_file() {
+                if file.remove(&provider).is_some() {
+                    write_fallback_file(&file)?;
+                }
+            }
+            Ok(())
+        }
+        SecretBackend::EncryptedFile => {
+            let mut file = read_fallback_file()?;
+            file.insert(provider, keys.to_vec());
+            write_fallback_file(&file)
+        }
+    }
+}
+
+fn cached_keys(
+    cache: &mut HashMap<ProviderKind, Vec<String>>,
+    provider: ProviderKind,
+) -> Result<&mut Vec<String>, String> {
+    match cache.entry(provider) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => Ok(entry.insert(load(provider)?)),
+    }
+}
+
+fn key_hint(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < 12 {
+        return "…".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("…{}", tail)
+}
+
+fn describe(provider: ProviderKind, key: &str) -> StoredKey {
+    StoredKey {
+        provider,
+        key_id: key_id(Some(key)),
+        hint: key_hint(key),
+        backend: backend(),
+    }
+}
+
+/// Stored keys for `provider`, in the order they were added.  Backend use
+/// only; never hand the result to the webview.
+pub fn api_keys(provider: ProviderKind) -> Result<Vec<String>, String> {
+    let mut cache = lock_cache();
+    cached_keys(&mut cache, provider).cloned()
+}
+
+/// Add `api_key` to the provider's pool.  Storing a key that is already
+/// there is a no-op.
+pub fn store_api_key(provider: ProviderKind, api_key: &str) -> Result<StoredKey, String> {
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        return Err("API key is empty".to_string());
+    }
+    if api_key.chars().any(char::is_whitespace) {
+        return Err("API key must not contain spaces or line breaks".to_string());
+    }
+
+    let mut cache = lock_cache();
+    let keys = cached_keys(&mut cache, provider)?;
+    if !keys.iter().any(|k| k == api_key) {
+        let mut updated = keys.clone();
+        updated.push(api_key.to_string());
+        save(provider, &updated)?;
+        *keys = updated;
+    }
+    Ok(describe(provider, api_key))
+}
+
+/// Every stored key, described but not revealed
+pub fn list_api_keys() -> Result<Vec<StoredKey>, String> {
+    let mut cache = lock_cache();
+    let mut stored = Vec::new();
+    for provider in ProviderKind::ALL {
+        for key in cached_keys(&mut cache, provider)?.iter() {
+            stored.push(describe(provider, key));
+        }
+    }
+    Ok(stored)
+}
+
+/// Remove one key by id, or every key for the provider when `key_id` is
+/// `None`.
+pub fn delete_api_key(provider: ProviderKind, id: Option<&str>) -> Result<(), String> {
+    let mut cache = lock_cache();
+    let keys = cached_keys(&mut cache, provider)?;
+    let updated: Vec<String> = match id {
+        Some(id) => {
+            if !keys.iter().any(|k| key_id(Some(k)) == id) {
+                return Err(format!("No stored {} key with id {}", provider, id));
+            }
+            keys.iter()
+                .filter(|k| key_id(Some(k)) != id)
+                .cloned()
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    save(provider, &updated)?;
+    *keys = updated;
+    Ok(())
+}
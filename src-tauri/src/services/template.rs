@@ -0,0 +1,402 @@
+// Prompt templates.  Rendering uses Jinja syntax with `${name}` for
+// variables, so the `${titleLimit}` placeholders of existing templates keep
+// working; `{name}` is accepted as a shorthand.  Blocks use the usual
+// `{% if %}`/`{% for %}` tags.  Variables come from the job limits, the file
+// path and the file's embedded metadata.
+use minijinja::syntax::SyntaxConfig;
+use minijinja::Environment;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use crate::models::jobs::MetadataLimits;
+use crate::models::metadata::ExifData;
+use crate::services::exiftool::read_exif_metadata;
+use crate::services::thumbnail::is_video_file;
+
+/// Every variable a template can use, with a short description for the
+/// template editor.  `render` and `validate` share this list.
+pub const TEMPLATE_VARIABLES: &[(&str, &str)] = &[
+    ("titleLimit", "Target title length in characters"),
+    (
+        "descriptionLimit",
+        "Target description length in characters",
+    ),
+    ("keywordLimit", "Number of keywords to generate"),
+    ("fileName", "File name with extension"),
+    ("folderName", "Name of the folder containing the file"),
+    ("currentDate", "Today's date (YYYY-MM-DD)"),
+    ("isVideo", "True for video files"),
+    ("camera", "Camera make and model, e.g. \"Canon EOS R5\""),
+    ("cameraMake", "Camera manufacturer"),
+    ("cameraModel", "Camera model"),
+    ("lens", "Lens model"),
+    ("captureDate", "Date the photo was taken (YYYY-MM-DD)"),
+    ("captureTime", "Time the photo was taken (HH:MM:SS)"),
+    ("captureYear", "Year the photo was taken"),
+    ("latitude", "GPS latitude in decimal degrees"),
+    ("longitude", "GPS longitude in decimal degrees"),
+    ("hasGps", "True when the file has GPS coordinates"),
+    (
+        "sublocation",
+        "Sub-location from IPTC/XMP (e.g. a park or street)",
+    ),
+    ("city", "City from IPTC/XMP location fields"),
+    ("state", "State or province from IPTC/XMP location fields"),
+    ("country", "Country from IPTC/XMP location fields"),
+    (
+        "place",
+        "Sub-location, city, state and country joined with commas",
+    ),
+    ("existingTitle", "Title already embedded in the file"),
+    (
+        "existingDescription",
+        "Description already embedded in the file",
+    ),
+    (
+        "existingKeywords",
+        "Keywords already embedded in the file (a list)",
+    ),
+];
+
+/// Names the engine provides itself; not typos.
+const BUILTIN_NAMES: &[&str] = &[
+    "loop",
+    "range",
+    "dict",
+    "namespace",
+    "debug",
+    "true",
+    "false",
+    "none",
+];
+
+/// Variables the app's templates have always required
+const REQUIRED_VARIABLES: &[&str] = &["titleLimit", "descriptionLimit", "keywordLimit"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub description: String,
+}
+
+/// Values for one render.  Serialised in camelCase to match the variable
+/// names templates already use.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateContext {
+    pub title_limit: usize,
+    pub description_limit: usize,
+    pub keyword_limit: usize,
+    pub file_name: Option<String>,
+    pub folder_name: Option<String>,
+    pub current_date: String,
+    pub is_video: bool,
+    pub camera: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    pub capture_date: Option<String>,
+    pub capture_time: Option<String>,
+    pub capture_year: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub has_gps: bool,
+    pub sublocation: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub place: Option<String>,
+    pub existing_title: Option<String>,
+    pub existing_description: Option<String>,
+    pub existing_keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateError {
+    /// 1-based line in the template as written
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "Template error on line {}: {}", line, self.message),
+            None => write!(f, "Template error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<minijinja::Error> for TemplateError {
+    fn from(e: minijinja::Error) -> Self {
+        let message = match e.detail() {
+            Some(detail) => format!("{} ({})", e.kind(), detail),
+            None => e.kind().to_string(),
+        };
+        TemplateError {
+            line: e.line(),
+            message,
+        }
+    }
+}
+
+/// Outcome of `validate`: errors stop the template from being saved,
+/// warnings are shown but allowed.  A template missing one of the limit
+/// variables is an error, as it always has been in the editor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateValidation {
+    pub valid: bool,
+    pub errors: Vec<TemplateError>,
+    pub warnings: Vec<String>,
+    /// Variables referenced that aren't in `TEMPLATE_VARIABLES`
+    pub unknown_variables: Vec<String>,
+    /// Required limit variables the template never mentions
+    pub missing_variables: Vec<String>,
+}
+
+pub fn template_variables() -> Vec<TemplateVariable> {
+    TEMPLATE_VARIABLES
+        .iter()
+        .map(|(name, description)| TemplateVariable {
+            name: name.to_string(),
+            description: description.to_string(),
+        })
+        .collect()
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    let syntax = SyntaxConfig::builder()
+        .variable_delimiters("${", "}")
+        .build()
+        .expect("static template syntax is valid");
+    env.set_syntax(syntax);
+    // Block tags on their own line shouldn't leave blank lines behind.
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    env
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Rewrite the `{name}` shorthand to `${name}`.  Only a bare identifier
+/// between single braces qualifies, so JSON examples such as
+/// `{"title": "..."}` in the prompt are left alone.
+fn expand_shorthand(template: &str) -> String {
+    let chars: Vec<char> = template.chars().collect();
+    let mut out = String::with_capacity(template.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+        if c == '{'
+            && !matches!(prev, Some('$') | Some('{'))
+            && chars.get(i + 1).is_some_and(|&n| is_name_start(n))
+        {
+            let end = (i + 1..chars.len())
+                .find(|&j| !is_name_char(chars[j]))
+                .unwrap_or(chars.len());
+            if chars.get(end) == Some(&'}') {
+                out.push('$');
+                out.extend(&chars[i..=end]);
+                i = end + 1;
+                continue;
+            }
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+impl TemplateContext {
+    /// Context for `file_path`.  Metadata is read with exiftool; a file it
+    /// can't read just leaves the EXIF variables empty.
+    pub fn for_file(file_path: &str, limits: &MetadataLimits) -> Self {
+        let exif = read_exif_metadata(file_path).unwrap_or_else(|e| {
+            eprintln!("[template] No metadata for {}: {}", file_path, e);
+            ExifData::default()
+        });
+        Self::from_exif(file_path, &exif, limits)
+    }
+
+    pub fn from_exif(file_path: &str, exif: &ExifData, limits: &MetadataLimits) -> Self {
+        let path = Path::new(file_path);
+        let camera_make = non_empty(exif.camera_make.as_ref());
+        let camera_model = non_empty(exif.camera_model.as_ref());
+        // Models often repeat the make ("Canon" + "Canon EOS R5").
+        let camera = match (&camera_make, &camera_model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => {
+                Some(model.clone())
+            }
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => model.clone().or_else(|| make.clone()),
+        };
+
+        let capture = non_empty(exif.capture_date.as_ref());
+        let (capture_date, capture_time) = match capture.as_deref().map(|d| d.split_once(' ')) {
+            Some(Some((date, time))) => (Some(date.to_string()), Some(time.to_string())),
+            Some(None) => (capture.clone(), None),
+            None => (None, None),
+        };
+
+        let sublocation = non_empty(exif.sublocation.as_ref());
+        let city = non_empty(exif.city.as_ref());
+        let state = non_empty(exif.state.as_ref());
+        let country = non_empty(exif.country.as_ref());
+        let parts: Vec<&str> = [&sublocation, &city, &state, &country]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        let place = (!parts.is_empty()).then(|| parts.join(", "));
+
+        let existing_keywords = exif
+            .keywords
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        TemplateContext {
+            title_limit: limits.title_limit,
+            description_limit: limits.description_limit,
+            keyword_limit: limits.keyword_limit,
+            file_name: path.file_name().map(|n| n.to_string_lossy().into_owned()),
+            folder_name: path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
+            current_date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            is_video: is_video_file(path),
+            camera,
+            camera_make,
+            camera_model,
+            lens: non_empty(exif.lens.as_ref()),
+            capture_year: capture_date
+                .as_ref()
+                .and_then(|d| d.get(..4))
+                .map(str::to_string),
+            capture_date,
+            capture_time,
+            latitude: exif.gps_latitude,
+            longitude: exif.gps_longitude,
+            has_gps: exif.gps_latitude.is_some() && exif.gps_longitude.is_some(),
+            sublocation,
+            city,
+            state,
+            country,
+            place,
+            existing_title: non_empty(exif.title.as_ref()),
+            existing_description: non_empty(exif.description.as_ref()),
+            existing_keywords,
+        }
+    }
+
+    /// Context with only the limits filled in, for previews without a file
+    pub fn with_limits(limits: &MetadataLimits) -> Self {
+        TemplateContext {
+            title_limit: limits.title_limit,
+            description_limit: limits.description_limit,
+            keyword_limit: limits.keyword_limit,
+            current_date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Render `template` with `context`.  Unknown or empty variables render as
+/// nothing, so test them with `{% if city %}` where the wording depends on
+/// them.
+pub fn render(template: &str, context: &TemplateContext) -> Result<String, TemplateError> {
+    let source = expand_shorthand(template);
+    let env = environment();
+    let rendered = env.render_str(&source, context)?;
+    Ok(rendered.trim().to_string())
+}
+
+/// Check a template before it is saved: syntax errors, misspelt variables
+/// and missing limit placeholders.
+pub fn validate(template: &str) -> TemplateValidation {
+    let source = expand_shorthand(template);
+    let env = environment();
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut unknown_variables = Vec::new();
+
+    if template.trim().is_empty() {
+        errors.push(TemplateError {
+            line: None,
+            message: "Template is empty".to_string(),
+        });
+    }
+
+    // Variable checks need a compiled template; with a syntax error the
+    // list would be empty and every limit reported missing.
+    let mut referenced = Vec::new();
+    let mut compiled_ok = false;
+    match env.template_from_str(&source) {
+        Ok(compiled) => {
+            referenced = compiled.undeclared_variables(false).into_iter().collect();
+            referenced.sort();
+            compiled_ok = true;
+            // Catches errors that only show up at render time, such as
+            // calling an unknown filter.
+            if let Err(e) =
+                compiled.render(TemplateContext::with_limits(&MetadataLimits::default()))
+            {
+                errors.push(e.into());
+            }
+        }
+        Err(e) => errors.push(e.into()),
+    }
+
+    for name in &referenced {
+        let known = TEMPLATE_VARIABLES.iter().any(|(v, _)| v == name)
+            || BUILTIN_NAMES.contains(&name.as_str());
+        if !known {
+            warnings.push(format!("Unknown variable \"{}\" will render empty", name));
+            unknown_variables.push(name.clone());
+        }
+    }
+
+    let missing_variables: Vec<String> = REQUIRED_VARIABLES
+        .iter()
+        .filter(|v| compiled_ok && !referenced.iter().any(|r| r == *v))
+        .map(|v| v.to_string())
+        .collect();
+    for name in &missing_variables {
+        errors.push(TemplateError {
+            line: None,
+            message: format!("Template must use ${{{}}}", name),
+        });
+    }
+
+    TemplateValidation {
+        valid: errors.is_empty(),
+        errors,
+        warnings,
+        unknown_variables,
+        missing_variables,
+    }
+}
@@ -3,29 +3,88 @@
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
 
 use base64::Engine;
 use blake3::Hasher;
 use image::imageops::FilterType;
-use image::GenericImageView;
+use image::metadata::Orientation;
 use image::{DynamicImage, ImageReader};
+use image::{GenericImageView, ImageDecoder};
 use lazy_static::lazy_static;
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
 
-use crate::services::gpu_thumbnail;
+use crate::services::gpu_thumbnail::VideoBackend;
+use crate::services::process::{self, RunOptions};
+use crate::services::tools::{tool_path, ExternalTool};
+use crate::services::video_probe;
+use crate::services::video_probe::VideoInfo;
 
 lazy_static! {
-    static ref VIDEO_GEN_LOCK: Mutex<()> = Mutex::new(());
+    static ref VIDEO_JOB_SLOTS: JobSlots = JobSlots::new(max_concurrent_video_jobs());
+}
+
+/// Upper bound on concurrent ffmpeg frame extractions.  Each one decodes a
+/// full video stream, so leave headroom for the image thumbnail workers.
+fn max_concurrent_video_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| (n.get() / 2).clamp(1, 4))
+        .unwrap_or(2)
+}
+
+/// Minimal blocking counting semaphore.  Video jobs run on tokio's blocking
+/// pool, so a `Condvar` is simpler here than an async semaphore.
+struct JobSlots {
+    active: Mutex<usize>,
+    released: Condvar,
+    limit: usize,
+}
+
+struct JobSlot<'a> {
+    slots: &'a JobSlots,
+}
+
+impl JobSlots {
+    fn new(limit: usize) -> Self {
+        Self {
+            active: Mutex::new(0),
+            released: Condvar::new(),
+            limit,
+        }
+    }
+
+    fn acquire(&self) -> JobSlot<'_> {
+        let mut active = self.active.lock().unwrap_or_else(|e| e.into_inner());
+        while *active >= self.limit {
+            active = self
+                .released
+                .wait(active)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        *active += 1;
+        JobSlot { slots: self }
+    }
+}
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        let mut active = self.slots.active.lock().unwrap_or_else(|e| e.into_inner());
+        *active -= 1;
+        self.slots.released.notify_one();
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ThumbnailResult {
-    /// Base64-encoded JPEG, populated **only** when the thumbnail could not be
-    /// written to disk (i.e. `cache_path` is None).  Prefer `cache_path`.
+    /// Base64-encoded image (JPEG unless another `OutputFormat` was requested),
+    /// populated **only** when the thumbnail could not be written to disk (i.e.
+    /// `cache_path` is None).  Prefer `cache_path`.
     pub thumbnail_base64: Option<String>,
-    /// Absolute path to the JPEG on disk inside the app's thumbnail cache dir.
+    /// Absolute path to the image on disk inside the app's thumbnail cache dir.
     /// The frontend should load this via `convertFileSrc` to avoid copying the
     /// image data through the IPC bridge and into the JS heap.
     pub cache_path: Option<String>,
@@ -37,16 +96,131 @@ pub struct ThumbnailResult {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PreviewResult {
-    /// Base64-encoded JPEG, populated **only** when the preview could not be
-    /// written to disk (i.e. `cache_path` is None).  Prefer `cache_path`.
+    /// Base64-encoded image (JPEG unless another `OutputFormat` was requested),
+    /// populated **only** when the preview could not be written to disk (i.e.
+    /// `cache_path` is None).  Prefer `cache_path`.
     pub preview_base64: Option<String>,
-    /// Absolute path to the JPEG on disk inside the app's thumbnail cache dir.
+    /// Absolute path to the image on disk inside the app's thumbnail cache dir.
     pub cache_path: Option<String>,
     pub width: Option<u32>,
     pub height: Option<u32>,
     pub from_cache: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Jpeg,
+    Webp,
+}
+
+/// Encoding settings for generated thumbnails and previews.  Every field is
+/// part of the cache key, so switching settings never serves a stale file.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct OutputOptions {
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// 1-100.  Ignored for lossless WebP.
+    #[serde(default = "default_output_quality")]
+    pub quality: u8,
+    /// WebP only: encode losslessly instead of using `quality`.
+    #[serde(default)]
+    pub lossless: bool,
+}
+
+fn default_output_quality() -> u8 {
+    70
+}
+
+/// libwebp rejects images with a side longer than this.
+const WEBP_MAX_DIMENSION: u32 = 16383;
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Jpeg,
+            quality: default_output_quality(),
+            lossless: false,
+        }
+    }
+}
+
+impl OutputOptions {
+    fn quality(&self) -> u8 {
+        self.quality.clamp(1, 100)
+    }
+
+    /// Settings for an image no larger than `target_size`.  WebP can't hold
+    /// sides over 16383px, so such sizes are encoded as JPEG.  Resolved
+    /// before the cache lookup so the file extension matches the contents.
+    fn for_target_size(&self, target_size: u32) -> OutputOptions {
+        if self.format == OutputFormat::Webp && target_size > WEBP_MAX_DIMENSION {
+            OutputOptions {
+                format: OutputFormat::Jpeg,
+                ..*self
+            }
+        } else {
+            *self
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+        }
+    }
+
+    /// Short tag appended to the cache key, e.g. `jpeg-q70` or `webp-lossless`.
+    fn cache_tag(&self) -> String {
+        match (self.format, self.lossless) {
+            (OutputFormat::Webp, true) => "webp-lossless".to_string(),
+            (OutputFormat::Webp, false) => format!("webp-q{}", self.quality()),
+            (OutputFormat::Jpeg, _) => format!("jpeg-q{}", self.quality()),
+        }
+    }
+}
+
+/// Resampling filter used when downscaling.  `Triangle` is fast and fine for
+/// grid tiles; the others trade speed for sharper large previews.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleFilter {
+    #[default]
+    Triangle,
+    CatmullRom,
+    Lanczos3,
+    /// Cheap box pre-shrink to roughly twice the target, then Lanczos3.
+    BoxLanczos3,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct ResizeOptions {
+    #[serde(default)]
+    pub filter: ResampleFilter,
+    /// Apply a light unsharp mask after downscaling.
+    #[serde(default)]
+    pub sharpen: bool,
+}
+
+impl ResizeOptions {
+    /// Short tag appended to the cache key, e.g. `lanczos3-sharp`.
+    fn cache_tag(&self) -> String {
+        let filter = match self.filter {
+            ResampleFilter::Triangle => "triangle",
+            ResampleFilter::CatmullRom => "catmullrom",
+            ResampleFilter::Lanczos3 => "lanczos3",
+            ResampleFilter::BoxLanczos3 => "box-lanczos3",
+        };
+        if self.sharpen {
+            format!("{}-sharp", filter)
+        } else {
+            filter.to_string()
+        }
+    }
+}
+
 fn get_app_thumbnail_cache_dir() -> Option<PathBuf> {
     dirs::cache_dir().map(|d| d.join("descify").join("thumbnails"))
 }
@@ -73,7 +247,13 @@ fn get_file_metadata(path: &str) -> Option<(u64, u64)> {
     Some((mtime, size))
 }
 
-fn read_image_from_file(path: &PathBuf) -> Option<DynamicImage> {
+/// A decoded source image together with its embedded ICC profile, if any.
+struct DecodedImage {
+    image: DynamicImage,
+    icc_profile: Option<Vec<u8>>,
+}
+
+fn read_image_from_file(path: &PathBuf) -> Option<DecodedImage> {
     let extension = path
         .extension()
         .and_then(|e| e.to_str())
@@ -98,6 +278,11 @@ fn read_image_from_file(path: &PathBuf) -> Option<DynamicImage> {
         return None;
     }
 
+    let is_heif = matches!(
+        extension.as_deref(),
+        Some("heic") | Some("heif") | Some("hif") | Some("avif")
+    );
+
     let file = File::open(path).ok()?;
     let file_size = file.metadata().ok()?.len();
 
@@ -110,14 +295,128 @@ fn read_image_from_file(path: &PathBuf) -> Option<DynamicImage> {
         Err(_) => return None,
     };
 
-    ImageReader::new(std::io::Cursor::new(&mmap))
+    // libheif already applies the container's rotation/mirroring on decode.
+    if is_heif {
+        return decode_heif(&mmap);
+    }
+
+    // Decode through the `ImageDecoder` so the EXIF Orientation tag can be read
+    // and applied before resizing; otherwise portrait shots come out sideways.
+    let mut decoder = ImageReader::new(std::io::Cursor::new(&mmap))
         .with_guessed_format()
         .ok()?
-        .decode()
-        .ok()
+        .into_decoder()
+        .ok()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc_profile = decoder.icc_profile().ok().flatten();
+
+    let mut img = DynamicImage::from_decoder(decoder).ok()?;
+    img.apply_orientation(orientation);
+    Some(DecodedImage {
+        image: img,
+        icc_profile,
+    })
+}
+
+/// Decodes HEIC/HEIF/AVIF containers through libheif, which the `image` crate
+/// cannot read on its own.  Only compiled in with the `heif` cargo feature.
+#[cfg(feature = "heif")]
+fn decode_heif(data: &[u8]) -> Option<DecodedImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_bytes(data).ok()?;
+    let handle = ctx.primary_image_handle().ok()?;
+    let has_alpha = handle.has_alpha_channel();
+    let icc_profile = handle.color_profile_raw().map(|p| p.data);
+    let chroma = if has_alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+
+    let decoded = lib_heif
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .ok()?;
+    let plane = decoded.planes().interleaved?;
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_len = plane.width as usize * channels;
+
+    // libheif pads rows to `stride`, so copy them out tightly packed.
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+
+    let image = if has_alpha {
+        image::RgbaImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        image::RgbImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgb8)
+    }?;
+
+    Some(DecodedImage { image, icc_profile })
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_data: &[u8]) -> Option<DecodedImage> {
+    None
+}
+
+/// Converts `img` from the colour space described by `icc_profile` to sRGB.
+///
+/// Wide-gamut sources (Adobe RGB, ProPhoto, Display P3) look washed out when
+/// their pixels are written to JPEG untouched.  Only RGB profiles are handled;
+/// anything else, or a profile that fails to parse, returns `img` unchanged.
+fn convert_to_srgb(img: DynamicImage, icc_profile: &[u8]) -> DynamicImage {
+    let source = match ColorProfile::new_from_slice(icc_profile) {
+        Ok(p) if p.color_space == DataColorSpace::Rgb => p,
+        _ => return img,
+    };
+    let srgb = ColorProfile::new_srgb();
+
+    let has_alpha = img.color().has_alpha();
+    let layout = if has_alpha { Layout::Rgba } else { Layout::Rgb };
+    let transform =
+        match source.create_transform_8bit(layout, &srgb, layout, TransformOptions::default()) {
+            Ok(t) => t,
+            Err(_) => return img,
+        };
+
+    let (width, height) = img.dimensions();
+    let src = if has_alpha {
+        img.to_rgba8().into_raw()
+    } else {
+        img.to_rgb8().into_raw()
+    };
+    let mut dst = vec![0u8; src.len()];
+    if transform.transform(&src, &mut dst).is_err() {
+        return img;
+    }
+
+    let converted = if has_alpha {
+        image::RgbaImage::from_raw(width, height, dst).map(DynamicImage::ImageRgba8)
+    } else {
+        image::RgbImage::from_raw(width, height, dst).map(DynamicImage::ImageRgb8)
+    };
+    converted.unwrap_or(img)
 }
 
-fn resize_image(img: &DynamicImage, target_size: u32) -> DynamicImage {
+/// Resizes a decoded image and converts it to sRGB when it carries an ICC
+/// profile.  Colour conversion runs after the resize so it only touches the
+/// (much smaller) output pixels.
+fn prepare_output_image(
+    decoded: &DecodedImage,
+    target_size: u32,
+    resize: &ResizeOptions,
+) -> DynamicImage {
+    let resized = resize_image(&decoded.image, target_size, resize);
+    match decoded.icc_profile.as_deref() {
+        Some(icc) => convert_to_srgb(resized, icc),
+        None => resized,
+    }
+}
+
+fn resize_image(img: &DynamicImage, target_size: u32, options: &ResizeOptions) -> DynamicImage {
     let (w, h) = img.dimensions();
     let max_dim = w.max(h);
 
@@ -129,22 +428,81 @@ fn resize_image(img: &DynamicImage, target_size: u32) -> DynamicImage {
     let new_w = (w as f32 * scale) as u32;
     let new_h = (h as f32 * scale) as u32;
 
-    img.resize(new_w, new_h, FilterType::Triangle)
+    let resized = match options.filter {
+        ResampleFilter::Triangle => img.resize(new_w, new_h, FilterType::Triangle),
+        ResampleFilter::CatmullRom => img.resize(new_w, new_h, FilterType::CatmullRom),
+        ResampleFilter::Lanczos3 => img.resize(new_w, new_h, FilterType::Lanczos3),
+        ResampleFilter::BoxLanczos3 => {
+            // Lanczos over a 24MP source is slow; area-average most of the way
+            // down first and only run the expensive filter over the last 2x.
+            if max_dim > target_size.saturating_mul(2) {
+                img.thumbnail(new_w * 2, new_h * 2)
+                    .resize(new_w, new_h, FilterType::Lanczos3)
+            } else {
+                img.resize(new_w, new_h, FilterType::Lanczos3)
+            }
+        }
+    };
+
+    if options.sharpen {
+        resized.unsharpen(0.6, 2)
+    } else {
+        resized
+    }
 }
 
-fn encode_jpeg_fast(img: &DynamicImage) -> Vec<u8> {
+fn encode_jpeg_fast(img: &DynamicImage, quality: u8) -> Vec<u8> {
     let rgb = img.to_rgb8();
     let (width, height) = rgb.dimensions();
     let mut buffer = Vec::with_capacity((width * height / 2) as usize);
-    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, 70);
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
     encoder.encode_image(&rgb).ok();
     buffer
 }
 
+/// `encode`/`encode_lossless` unwrap internally, so go through
+/// `encode_simple` and let the caller handle the error.
+fn encode_webp(img: &DynamicImage, output: &OutputOptions) -> Option<Vec<u8>> {
+    let (width, height) = img.dimensions();
+    let quality = if output.lossless {
+        75.0
+    } else {
+        output.quality() as f32
+    };
+    let memory = if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        webp::Encoder::from_rgba(rgba.as_raw(), width, height)
+            .encode_simple(output.lossless, quality)
+    } else {
+        let rgb = img.to_rgb8();
+        webp::Encoder::from_rgb(rgb.as_raw(), width, height).encode_simple(output.lossless, quality)
+    };
+    match memory {
+        Ok(m) => Some(m.to_vec()),
+        Err(e) => {
+            eprintln!("[thumbnail] WebP encoding failed ({:?}), using JPEG", e);
+            None
+        }
+    }
+}
+
+fn encode_image(img: &DynamicImage, output: &OutputOptions) -> Vec<u8> {
+    match output.format {
+        OutputFormat::Jpeg => encode_jpeg_fast(img, output.quality()),
+        OutputFormat::Webp => {
+            encode_webp(img, output).unwrap_or_else(|| encode_jpeg_fast(img, output.quality()))
+        }
+    }
+}
+
 /// Returns the path to a cached thumbnail file if it already exists on disk,
 /// without reading or decoding the file contents (avoids unnecessary I/O).
-fn get_cached_thumbnail_path(cache_dir: &PathBuf, cache_key: &str) -> Option<PathBuf> {
-    let thumb_path = cache_dir.join(format!("{}.jpg", cache_key));
+fn get_cached_thumbnail_path(
+    cache_dir: &PathBuf,
+    cache_key: &str,
+    extension: &str,
+) -> Option<PathBuf> {
+    let thumb_path = cache_dir.join(format!("{}.{}", cache_key, extension));
     if thumb_path.exists() {
         Some(thumb_path)
     } else {
@@ -152,24 +510,42 @@ fn get_cached_thumbnail_path(cache_dir: &PathBuf, cache_key: &str) -> Option<Pat
     }
 }
 
-/// Writes `jpeg_data` to the cache directory and returns the resulting path on
+/// Writes `data` to a uniquely named temp file next to `path` and renames
+/// it into place, so a concurrent reader of the same key never sees a
+/// partially written file.
+fn write_cache_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let result = File::create(&tmp_path)
+        .and_then(|mut file| file.write_all(data))
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Writes `data` to the cache directory and returns the resulting path on
 /// success, or `None` if the write failed.  Callers fall back to returning
 /// base64-encoded data when this function returns `None`.
 fn save_thumbnail_to_cache(
     cache_dir: &PathBuf,
     cache_key: &str,
-    jpeg_data: &[u8],
+    extension: &str,
+    data: &[u8],
 ) -> Option<PathBuf> {
     if !cache_dir.exists() {
         fs::create_dir_all(cache_dir).ok()?;
     }
-    let thumb_path = cache_dir.join(format!("{}.jpg", cache_key));
-    let mut file = File::create(&thumb_path).ok()?;
-    file.write_all(jpeg_data).ok()?;
+    let thumb_path = cache_dir.join(format!("{}.{}", cache_key, extension));
+    write_cache_file(&thumb_path, data).ok()?;
     Some(thumb_path)
 }
 
-pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult {
+pub fn generate_thumbnail(
+    file_path: &str,
+    target_size: u32,
+    output: &OutputOptions,
+) -> ThumbnailResult {
     let path = PathBuf::from(file_path);
     if !path.exists() || !path.is_file() {
         return ThumbnailResult {
@@ -196,8 +572,14 @@ pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult
         }
     };
     let (file_mtime, file_size) = file_meta;
+    let output = &output.for_target_size(target_size);
 
-    let cache_key = compute_cache_key(file_path, file_mtime, file_size);
+    let cache_key = format!(
+        "{}_{}_{}",
+        compute_cache_key(file_path, file_mtime, file_size),
+        target_size,
+        output.cache_tag()
+    );
     let cache_dir = match get_app_thumbnail_cache_dir() {
         Some(d) => d,
         None => {
@@ -214,7 +596,8 @@ pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult
 
     // Cache hit: return the on-disk path without reading or re-encoding the file.
     // The frontend loads it via the asset:// protocol (convertFileSrc).
-    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key) {
+    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key, output.extension())
+    {
         return ThumbnailResult {
             thumbnail_base64: None,
             cache_path: Some(cached_path.to_string_lossy().into_owned()),
@@ -225,7 +608,7 @@ pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult
         };
     }
 
-    let img = match read_image_from_file(&path) {
+    let decoded = match read_image_from_file(&path) {
         Some(i) => i,
         None => {
             return ThumbnailResult {
@@ -239,18 +622,18 @@ pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult
         }
     };
 
-    let resized = resize_image(&img, target_size);
+    let resized = prepare_output_image(&decoded, target_size, &ResizeOptions::default());
     let (width, height) = resized.dimensions();
-    let jpeg_data = encode_jpeg_fast(&resized);
+    let encoded = encode_image(&resized, output);
 
     // Prefer returning a path so the image never has to cross the IPC bridge as
     // base64.  Fall back to base64 only if the disk write failed.
     let (cache_path, thumbnail_base64) =
-        match save_thumbnail_to_cache(&cache_dir, &cache_key, &jpeg_data) {
+        match save_thumbnail_to_cache(&cache_dir, &cache_key, output.extension(), &encoded) {
             Some(p) => (Some(p.to_string_lossy().into_owned()), None),
             None => (
                 None,
-                Some(base64::engine::general_purpose::STANDARD.encode(&jpeg_data)),
+                Some(base64::engine::general_purpose::STANDARD.encode(&encoded)),
             ),
         };
 
@@ -264,7 +647,12 @@ pub fn generate_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult
     }
 }
 
-pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
+pub fn generate_preview(
+    file_path: &str,
+    target_size: u32,
+    output: &OutputOptions,
+    resize: &ResizeOptions,
+) -> PreviewResult {
     let path = PathBuf::from(file_path);
     if !path.exists() || !path.is_file() {
         return PreviewResult {
@@ -289,8 +677,15 @@ pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
         }
     };
     let (file_mtime, _file_size) = file_meta;
+    let output = &output.for_target_size(target_size);
 
-    let cache_key = format!("preview_{}", compute_cache_key(file_path, file_mtime, 0));
+    let cache_key = format!(
+        "preview_{}_{}_{}_{}",
+        compute_cache_key(file_path, file_mtime, 0),
+        target_size,
+        resize.cache_tag(),
+        output.cache_tag()
+    );
     let cache_dir = match get_app_thumbnail_cache_dir() {
         Some(d) => d,
         None => {
@@ -304,7 +699,8 @@ pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
         }
     };
 
-    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key) {
+    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key, output.extension())
+    {
         return PreviewResult {
             preview_base64: None,
             cache_path: Some(cached_path.to_string_lossy().into_owned()),
@@ -314,7 +710,7 @@ pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
         };
     }
 
-    let img = match read_image_from_file(&path) {
+    let decoded = match read_image_from_file(&path) {
         Some(i) => i,
         None => {
             return PreviewResult {
@@ -327,16 +723,16 @@ pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
         }
     };
 
-    let resized = resize_image(&img, target_size);
+    let resized = prepare_output_image(&decoded, target_size, resize);
     let (width, height) = resized.dimensions();
-    let jpeg_data = encode_jpeg_fast(&resized);
+    let encoded = encode_image(&resized, output);
 
     let (cache_path, preview_base64) =
-        match save_thumbnail_to_cache(&cache_dir, &cache_key, &jpeg_data) {
+        match save_thumbnail_to_cache(&cache_dir, &cache_key, output.extension(), &encoded) {
             Some(p) => (Some(p.to_string_lossy().into_owned()), None),
             None => (
                 None,
-                Some(base64::engine::general_purpose::STANDARD.encode(&jpeg_data)),
+                Some(base64::engine::general_purpose::STANDARD.encode(&encoded)),
             ),
         };
 
@@ -349,79 +745,178 @@ pub fn generate_preview(file_path: &str, target_size: u32) -> PreviewResult {
     }
 }
 
-fn get_ffmpeg_path() -> Option<PathBuf> {
-    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
-    let ffmpeg = exe_dir.join("ffmpeg-x86_64-unknown-linux-gnu-x86_64-unknown-linux-gnu");
-    if ffmpeg.exists() {
-        return Some(ffmpeg);
-    }
-    let ffmpeg = exe_dir.join("ffmpeg");
-    if ffmpeg.exists() {
-        return Some(ffmpeg);
-    }
-    which::which("ffmpeg").ok()
+pub fn get_ffmpeg_path() -> Option<PathBuf> {
+    tool_path(ExternalTool::Ffmpeg)
 }
 
-fn detect_video_dimensions(file_path: &str) -> Option<(u32, u32)> {
-    let ffmpeg = get_ffmpeg_path()?;
-    let output = Command::new(&ffmpeg)
-        .args(["-i", file_path, "-hide_banner"])
-        .output()
-        .ok()?;
+pub fn is_video_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    for line in stderr.lines() {
-        if line.contains("Video:") || line.contains("Stream #") {
-            if let Some(res) = line.split("x").last() {
-                let dims: Vec<u32> = res
-                    .split_whitespace()
-                    .next()?
-                    .split(',')
-                    .next()?
-                    .split('x')
-                    .filter_map(|s| s.trim().parse().ok())
-                    .collect();
-                if dims.len() >= 2 {
-                    return Some((dims[0], dims[1]));
-                }
+    matches!(
+        extension.as_deref(),
+        Some("mp4")
+            | Some("mov")
+            | Some("webm")
+            | Some("avi")
+            | Some("mkv")
+            | Some("flv")
+            | Some("wmv")
+            | Some("m4v")
+            | Some("3gp")
+            | Some("ogv")
+            | Some("mts")
+            | Some("m2ts")
+    )
+}
+
+/// A single seek-and-decode should finish in well under a second; a
+/// corrupt file can otherwise keep ffmpeg spinning forever.
+const FRAME_EXTRACTION_TIMEOUT: Duration = Duration::from_secs(30);
+/// Scene detection decodes the whole video, so it gets far longer.
+const SCENE_DETECTION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// ffmpeg output arguments that write a single JPEG frame to stdout.
+const PIPE_OUTPUT_ARGS: [&str; 5] = ["-f", "image2pipe", "-vcodec", "mjpeg", "pipe:1"];
+
+/// Video info for sizing and seeking frames.  Without it frames are sized
+/// as if the video were 1920x1080 with an unknown duration, so the reason is
+/// logged: once if ffprobe is missing, otherwise per file.
+fn probe_video_for_frames(file_path: &str) -> Option<VideoInfo> {
+    static WARNED_MISSING_FFPROBE: AtomicBool = AtomicBool::new(false);
+
+    match video_probe::probe_video(file_path) {
+        Ok(info) => Some(info),
+        Err(_) if video_probe::get_ffprobe_path().is_none() => {
+            if !WARNED_MISSING_FFPROBE.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "[thumbnail] ffprobe not found; video frames assume 1920x1080 and pick seek times without a duration"
+                );
             }
+            None
+        }
+        Err(e) => {
+            eprintln!(
+                "[thumbnail] Could not probe {}: {}; assuming 1920x1080",
+                file_path, e
+            );
+            None
         }
     }
-    None
 }
 
-fn generate_video_thumbnail_ffmpeg(
-    file_path: &str,
-    target_size: u32,
-) -> Option<(Vec<u8>, u32, u32)> {
-    let ffmpeg = get_ffmpeg_path()?;
-
-    let (orig_w, orig_h) = detect_video_dimensions(file_path).unwrap_or((1920, 1080));
+/// Output frame size for a video scaled to fit within `target_size`.
+fn scaled_video_size(info: Option<&VideoInfo>, target_size: u32) -> (u32, u32) {
+    // ffmpeg auto-rotates decoded frames, so scale against the displayed size.
+    let (orig_w, orig_h) = info.map(|i| i.display_dimensions()).unwrap_or((1920, 1080));
     let scale = if orig_w > orig_h {
         (target_size as f32 / orig_w as f32).min(1.0)
     } else {
         (target_size as f32 / orig_h as f32).min(1.0)
     };
-    let new_w = (orig_w as f32 * scale) as u32;
-    let new_h = (orig_h as f32 * scale) as u32;
+    // A very wide or tall video can round its short side down to nothing.
+    (
+        ((orig_w as f32 * scale) as u32).max(1),
+        ((orig_h as f32 * scale) as u32).max(1),
+    )
+}
 
-    let temp_dir = std::env::temp_dir();
-    let output_path = temp_dir.join(format!("thumb_{}.jpg", std::process::id()));
+/// Seek positions (in seconds) to try when picking a representative frame.
+///
+/// Long clips are sampled at 10/30/50/70% so fade-ins and title cards don't
+/// win by default.  Very short clips only try the midpoint, and clips with an
+/// unknown duration keep the old one-second seek.  The first frame is always
+/// the last resort, so even sub-second clips produce a thumbnail.
+fn candidate_seek_times(duration_secs: Option<f64>) -> Vec<f64> {
+    let mut times = match duration_secs {
+        Some(d) if d >= 2.0 => vec![d * 0.1, d * 0.3, d * 0.5, d * 0.7],
+        Some(d) if d > 0.2 => vec![d * 0.5],
+        Some(_) => Vec::new(),
+        None => vec![1.0],
+    };
+    times.push(0.0);
+    times
+}
 
-    let use_gpu = gpu_thumbnail::is_gpu_available();
-    let hwaccel_args = gpu_thumbnail::get_ffmpeg_hwaccel_args();
+/// Scores a decoded frame for use as a thumbnail: well-exposed, contrasty
+/// and sharp frames score highest, near-black or blown-out ones near zero.
+fn score_frame(img: &DynamicImage) -> f32 {
+    let luma = img.thumbnail(128, 128).to_luma8();
+    let (w, h) = luma.dimensions();
+    if w < 3 || h < 3 {
+        return 0.0;
+    }
 
-    let filter_str = if use_gpu {
-        format!("scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:black,hwupload_cuda", new_w, new_h, new_w, new_h)
-    } else {
-        format!(
-            "scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:black",
-            new_w, new_h, new_w, new_h
-        )
-    };
+    let pixels = luma.as_raw();
+    let count = pixels.len() as f32;
+    let mean = pixels.iter().map(|&p| p as f32).sum::<f32>() / count;
+    let variance = pixels
+        .iter()
+        .map(|&p| (p as f32 - mean).powi(2))
+        .sum::<f32>()
+        / count;
+
+    // Mean absolute Laplacian as a cheap focus measure.
+    let mut laplacian = 0.0f32;
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let c = luma.get_pixel(x, y)[0] as f32 * 4.0;
+            let n = luma.get_pixel(x, y - 1)[0] as f32
+                + luma.get_pixel(x, y + 1)[0] as f32
+                + luma.get_pixel(x - 1, y)[0] as f32
+                + luma.get_pixel(x + 1, y)[0] as f32;
+            laplacian += (c - n).abs();
+        }
+    }
+    laplacian /= ((w - 2) * (h - 2)) as f32;
+
+    let exposure = 1.0 - ((mean - 128.0).abs() / 128.0);
+    let contrast = (variance.sqrt() / 64.0).min(1.0);
+    let sharpness = (laplacian / 32.0).min(1.0);
+
+    if !(16.0..=240.0).contains(&mean) {
+        return exposure * 0.1;
+    }
+    exposure * 0.3 + contrast * 0.35 + sharpness * 0.35
+}
+
+/// Extracts a single scaled JPEG frame at `seek_secs`, trying the current
+/// hardware backend first and falling back to a plain CPU decode.
+fn extract_video_frame(
+    ffmpeg: &Path,
+    file_path: &str,
+    seek_secs: f64,
+    new_w: u32,
+    new_h: u32,
+    backend: VideoBackend,
+) -> Option<Vec<u8>> {
+    let seek = format!("{:.3}", seek_secs);
+
+    if let Some(data) = run_frame_extraction(ffmpeg, file_path, &seek, backend, new_w, new_h) {
+        return Some(data);
+    }
+
+    // Hardware decoders reject some codecs and profiles; retry on the CPU.
+    if backend.is_hardware() {
+        return run_frame_extraction(ffmpeg, file_path, &seek, VideoBackend::Cpu, new_w, new_h);
+    }
+    None
+}
 
-    let mut args = vec!["-y".to_string(), "-ss".to_string(), "00:00:01".to_string()];
-    for arg in hwaccel_args.iter() {
+fn run_frame_extraction(
+    ffmpeg: &Path,
+    file_path: &str,
+    seek: &str,
+    backend: VideoBackend,
+    new_w: u32,
+    new_h: u32,
+) -> Option<Vec<u8>> {
+    // The frame is streamed to stdout instead of a shared temp file, so
+    // concurrent jobs never clobber each other's output.
+    let mut args = vec!["-y".to_string(), "-ss".to_string(), seek.to_string()];
+    for arg in backend.hwaccel_args() {
         args.push(arg.to_string());
     }
     args.push("-i".to_string());
@@ -429,59 +924,68 @@ fn generate_video_thumbnail_ffmpeg(
     args.push("-vframes".to_string());
     args.push("1".to_string());
     args.push("-vf".to_string());
-    args.push(filter_str);
+    args.push(backend.scale_filter(new_w, new_h));
     args.push("-q:v".to_string());
     args.push("2".to_string());
-    args.push(output_path.to_str()?.to_string());
-
-    let output = Command::new(&ffmpeg).args(&args).output().ok()?;
-
-    if !output.status.success() {
-        let fallback_output = temp_dir.join(format!("thumb_fallback_{}.jpg", std::process::id()));
-        let fallback_filter = format!("scale={}:{}", new_w, new_h);
-        let output = Command::new(&ffmpeg)
-            .args([
-                "-y", "-ss", "00:00:01", "-i", file_path, "-vframes", "1", "-vf",
-            ])
-            .arg(&fallback_filter)
-            .args(["-q:v", "2"])
-            .arg(fallback_output.to_str()?)
-            .output()
-            .ok()?;
-
-        if !output.status.success() {
-            return None;
-        }
+    args.extend(PIPE_OUTPUT_ARGS.iter().map(|a| a.to_string()));
 
-        let data = fs::read(&fallback_output).ok()?;
-        let _ = fs::remove_file(&fallback_output);
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(&args);
+    let output = process::run(cmd, &RunOptions::with_timeout(FRAME_EXTRACTION_TIMEOUT)).ok()?;
+    if !output.success() || output.stdout.is_empty() {
+        return None;
+    }
+    Some(output.stdout)
+}
 
-        let img = ImageReader::new(std::io::Cursor::new(&data))
-            .with_guessed_format()
-            .ok()?
-            .decode()
-            .ok()?;
+fn generate_video_thumbnail_ffmpeg(
+    file_path: &str,
+    target_size: u32,
+    backend: VideoBackend,
+) -> Option<(Vec<u8>, u32, u32)> {
+    let ffmpeg = get_ffmpeg_path()?;
+    let _slot = VIDEO_JOB_SLOTS.acquire();
 
-        let (w, h) = img.dimensions();
-        return Some((data, w, h));
-    }
+    let info = probe_video_for_frames(file_path);
+    let (new_w, new_h) = scaled_video_size(info.as_ref(), target_size);
 
-    let data = fs::read(&output_path).ok()?;
-    let _ = fs::remove_file(&output_path);
+    let seek_times = candidate_seek_times(info.as_ref().and_then(|i| i.duration_secs));
+    let sampled = seek_times.len() - 1;
 
-    let img = ImageReader::new(std::io::Cursor::new(&data))
-        .with_guessed_format()
-        .ok()?
-        .decode()
-        .ok()?;
+    // Score every sampled frame and keep the best one.  The trailing
+    // first-frame seek is only used when none of the samples decoded.
+    let mut best: Option<(f32, Vec<u8>, u32, u32)> = None;
+    for (i, seek) in seek_times.into_iter().enumerate() {
+        if i == sampled && best.is_some() {
+            break;
+        }
+        let Some(data) = extract_video_frame(&ffmpeg, file_path, seek, new_w, new_h, backend)
+        else {
+            continue;
+        };
+        let Some(img) = ImageReader::new(std::io::Cursor::new(&data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|r| r.decode().ok())
+        else {
+            continue;
+        };
 
-    let (w, h) = img.dimensions();
-    Some((data, w, h))
-}
+        let score = score_frame(&img);
+        if best.as_ref().is_none_or(|(s, ..)| score > *s) {
+            let (w, h) = img.dimensions();
+            best = Some((score, data, w, h));
+        }
+    }
 
-pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailResult {
-    let _guard = VIDEO_GEN_LOCK.lock().unwrap();
+    best.map(|(_, data, w, h)| (data, w, h))
+}
 
+pub fn generate_video_thumbnail(
+    file_path: &str,
+    target_size: u32,
+    backend: VideoBackend,
+) -> ThumbnailResult {
     let path = PathBuf::from(file_path);
     if !path.exists() || !path.is_file() {
         return ThumbnailResult {
@@ -494,28 +998,7 @@ pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailR
         };
     }
 
-    let extension = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
-
-    let is_video = matches!(
-        extension.as_deref(),
-        Some("mp4")
-            | Some("mov")
-            | Some("webm")
-            | Some("avi")
-            | Some("mkv")
-            | Some("flv")
-            | Some("wmv")
-            | Some("m4v")
-            | Some("3gp")
-            | Some("ogv")
-            | Some("mts")
-            | Some("m2ts")
-    );
-
-    if !is_video {
+    if !is_video_file(&path) {
         return ThumbnailResult {
             thumbnail_base64: None,
             cache_path: None,
@@ -542,8 +1025,9 @@ pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailR
     let (file_mtime, file_size) = file_meta;
 
     let cache_key = format!(
-        "video_{}",
-        compute_cache_key(file_path, file_mtime, file_size)
+        "video_{}_{}",
+        compute_cache_key(file_path, file_mtime, file_size),
+        target_size
     );
     let cache_dir = match get_app_thumbnail_cache_dir() {
         Some(d) => d,
@@ -559,7 +1043,7 @@ pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailR
         }
     };
 
-    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key) {
+    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key, "jpg") {
         return ThumbnailResult {
             thumbnail_base64: None,
             cache_path: Some(cached_path.to_string_lossy().into_owned()),
@@ -570,22 +1054,23 @@ pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailR
         };
     }
 
-    let (jpeg_data, width, height) = match generate_video_thumbnail_ffmpeg(file_path, target_size) {
-        Some(result) => result,
-        None => {
-            return ThumbnailResult {
-                thumbnail_base64: None,
-                cache_path: None,
-                width: None,
-                height: None,
-                file_size: Some(file_size),
-                from_cache: false,
+    let (jpeg_data, width, height) =
+        match generate_video_thumbnail_ffmpeg(file_path, target_size, backend) {
+            Some(result) => result,
+            None => {
+                return ThumbnailResult {
+                    thumbnail_base64: None,
+                    cache_path: None,
+                    width: None,
+                    height: None,
+                    file_size: Some(file_size),
+                    from_cache: false,
+                }
             }
-        }
-    };
+        };
 
     let (cache_path, thumbnail_base64) =
-        match save_thumbnail_to_cache(&cache_dir, &cache_key, &jpeg_data) {
+        match save_thumbnail_to_cache(&cache_dir, &cache_key, "jpg", &jpeg_data) {
             Some(p) => (Some(p.to_string_lossy().into_owned()), None),
             None => (
                 None,
@@ -603,9 +1088,11 @@ pub fn generate_video_thumbnail(file_path: &str, target_size: u32) -> ThumbnailR
     }
 }
 
-pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResult {
-    let _guard = VIDEO_GEN_LOCK.lock().unwrap();
-
+pub fn generate_video_preview(
+    file_path: &str,
+    target_size: u32,
+    backend: VideoBackend,
+) -> PreviewResult {
     let path = PathBuf::from(file_path);
     if !path.exists() || !path.is_file() {
         return PreviewResult {
@@ -617,28 +1104,7 @@ pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResul
         };
     }
 
-    let extension = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
-
-    let is_video = matches!(
-        extension.as_deref(),
-        Some("mp4")
-            | Some("mov")
-            | Some("webm")
-            | Some("avi")
-            | Some("mkv")
-            | Some("flv")
-            | Some("wmv")
-            | Some("m4v")
-            | Some("3gp")
-            | Some("ogv")
-            | Some("mts")
-            | Some("m2ts")
-    );
-
-    if !is_video {
+    if !is_video_file(&path) {
         return PreviewResult {
             preview_base64: None,
             cache_path: None,
@@ -663,8 +1129,9 @@ pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResul
     let (file_mtime, _file_size) = file_meta;
 
     let cache_key = format!(
-        "video_preview_{}",
-        compute_cache_key(file_path, file_mtime, 0)
+        "video_preview_{}_{}",
+        compute_cache_key(file_path, file_mtime, 0),
+        target_size
     );
     let cache_dir = match get_app_thumbnail_cache_dir() {
         Some(d) => d,
@@ -679,7 +1146,7 @@ pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResul
         }
     };
 
-    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key) {
+    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key, "jpg") {
         return PreviewResult {
             preview_base64: None,
             cache_path: Some(cached_path.to_string_lossy().into_owned()),
@@ -689,21 +1156,22 @@ pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResul
         };
     }
 
-    let (jpeg_data, width, height) = match generate_video_thumbnail_ffmpeg(file_path, target_size) {
-        Some(result) => result,
-        None => {
-            return PreviewResult {
-                preview_base64: None,
-                cache_path: None,
-                width: None,
-                height: None,
-                from_cache: false,
+    let (jpeg_data, width, height) =
+        match generate_video_thumbnail_ffmpeg(file_path, target_size, backend) {
+            Some(result) => result,
+            None => {
+                return PreviewResult {
+                    preview_base64: None,
+                    cache_path: None,
+                    width: None,
+                    height: None,
+                    from_cache: false,
+                }
             }
-        }
-    };
+        };
 
     let (cache_path, preview_base64) =
-        match save_thumbnail_to_cache(&cache_dir, &cache_key, &jpeg_data) {
+        match save_thumbnail_to_cache(&cache_dir, &cache_key, "jpg", &jpeg_data) {
             Some(p) => (Some(p.to_string_lossy().into_owned()), None),
             None => (
                 None,
@@ -719,3 +1187,379 @@ pub fn generate_video_preview(file_path: &str, target_size: u32) -> PreviewResul
         from_cache: false,
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyframeSelection {
+    /// Frames spread evenly across the clip's duration.
+    #[default]
+    Even,
+    /// Frames where ffmpeg's scene-change score jumps, topped up with evenly
+    /// spaced frames when the clip has fewer cuts than requested.
+    SceneChange,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VideoKeyframesResult {
+    /// Absolute paths to the extracted JPEG frames, in playback order.
+    pub frame_paths: Vec<String>,
+    /// Absolute path to a tiled JPEG of all frames, when one was requested.
+    pub contact_sheet_path: Option<String>,
+    pub from_cache: bool,
+}
+
+/// Splits a concatenated MJPEG stream (as written by `image2pipe`) into
+/// individual JPEG files.  ffmpeg byte-stuffs entropy-coded data, so an
+/// `FFD9` marker only ever appears at the end of a frame.
+fn split_mjpeg_stream(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    let mut start = None;
+    let mut i = 0;
+    while i + 1 < data.len() {
+        match (data[i], data[i + 1]) {
+            (0xFF, 0xD8) if start.is_none() => {
+                start = Some(i);
+                i += 2;
+            }
+            (0xFF, 0xD9) => {
+                if let Some(s) = start.take() {
+                    frames.push(data[s..i + 2].to_vec());
+                }
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    frames
+}
+
+/// Extracts up to `count` frames at scene cuts in a single ffmpeg pass,
+/// each with its timestamp in seconds.  `showinfo` logs one `pts_time:`
+/// line per selected frame to stderr; if those can't be matched up with
+/// the frames, none are returned and the caller falls back to evenly
+/// spaced frames.
+fn extract_scene_change_frames(
+    ffmpeg: &Path,
+    file_path: &str,
+    count: u32,
+    new_w: u32,
+    new_h: u32,
+) -> Vec<(f64, Vec<u8>)> {
+    let filter = format!("select='gt(scene,0.3)',showinfo,scale={}:{}", new_w, new_h);
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(["-i", file_path, "-vf", &filter, "-vsync", "vfr"])
+        .args(["-frames:v", &count.to_string(), "-q:v", "2"])
+        .args(PIPE_OUTPUT_ARGS);
+
+    // Scene detection decodes the whole clip, so it gets a longer budget.
+    let output = match process::run(cmd, &RunOptions::with_timeout(SCENE_DETECTION_TIMEOUT)) {
+        Ok(o) if o.success() => o,
+        _ => return Vec::new(),
+    };
+    let frames = split_mjpeg_stream(&output.stdout);
+    let times = showinfo_timestamps(&String::from_utf8_lossy(&output.stderr));
+    if times.len() != frames.len() {
+        eprintln!(
+            "[keyframes] {} scene frames but {} timestamps for {}",
+            frames.len(),
+            times.len(),
+            file_path
+        );
+        return Vec::new();
+    }
+    times.into_iter().zip(frames).collect()
+}
+
+/// `pts_time` of every frame logged by the `showinfo` filter.
+fn showinfo_timestamps(stderr: &str) -> Vec<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("Parsed_showinfo"))
+        .filter_map(|line| {
+            let rest = &line[line.find("pts_time:")? + "pts_time:".len()..];
+            rest.split_whitespace().next()?.parse::<f64>().ok()
+        })
+        .collect()
+}
+
+/// Evenly spaced seek positions, each centred in its slice of the clip.
+fn even_seek_times(duration_secs: f64, count: u32) -> Vec<f64> {
+    (0..count)
+        .map(|i| duration_secs * (i as f64 + 0.5) / count as f64)
+        .collect()
+}
+
+/// Tiles `frames` into a roughly square grid on a black background.
+fn build_contact_sheet(frames: &[DynamicImage]) -> Option<DynamicImage> {
+    const GAP: u32 = 4;
+
+    let count = frames.len() as u32;
+    if count == 0 {
+        return None;
+    }
+    let cols = (count as f64).sqrt().ceil() as u32;
+    let rows = count.div_ceil(cols);
+    let tile_w = frames.iter().map(|f| f.width()).max()?;
+    let tile_h = frames.iter().map(|f| f.height()).max()?;
+
+    let mut sheet = image::RgbImage::new(
+        cols * tile_w + (cols + 1) * GAP,
+        rows * tile_h + (rows + 1) * GAP,
+    );
+    for (i, frame) in frames.iter().enumerate() {
+        let col = i as u32 % cols;
+        let row = i as u32 / cols;
+        let x = GAP + col * (tile_w + GAP) + (tile_w - frame.width()) / 2;
+        let y = GAP + row * (tile_h + GAP) + (tile_h - frame.height()) / 2;
+        image::imageops::overlay(&mut sheet, &frame.to_rgb8(), x as i64, y as i64);
+    }
+    Some(DynamicImage::ImageRgb8(sheet))
+}
+
+fn write_contact_sheet(frames: &[DynamicImage], sheet_path: &Path) -> Result<(), String> {
+    let sheet =
+        build_contact_sheet(frames).ok_or_else(|| "Failed to build contact sheet".to_string())?;
+    write_cache_file(sheet_path, &encode_jpeg_fast(&sheet, 85))
+        .map_err(|e| format!("Failed to write contact sheet: {}", e))
+}
+
+/// Extracts `count` frames from a video for AI analysis and caches them as
+/// JPEGs, optionally together with a single tiled contact sheet so the whole
+/// clip can be sent to a model as one image.
+///
+/// A JSON manifest is written last and doubles as the cache-hit marker, so a
+/// run interrupted half way through is simply redone.
+pub fn extract_video_keyframes(
+    file_path: &str,
+    count: u32,
+    target_size: u32,
+    selection: KeyframeSelection,
+    contact_sheet: bool,
+    backend: VideoBackend,
+) -> Result<VideoKeyframesResult, String> {
+    let path = PathBuf::from(file_path);
+    if !path.exists() || !path.is_file() {
+        return Err(format!("File does not exist: {}", file_path));
+    }
+    if !is_video_file(&path) {
+        return Err(format!("Not a supported video file: {}", file_path));
+    }
+    let count = count.clamp(1, 24);
+
+    let (file_mtime, file_size) = get_file_metadata(file_path)
+        .ok_or_else(|| format!("Failed to read file metadata: {}", file_path))?;
+    let cache_dir = get_app_thumbnail_cache_dir()
+        .ok_or_else(|| "Could not determine the thumbnail cache directory".to_string())?;
+
+    let mode = match selection {
+        KeyframeSelection::Even => "even",
+        KeyframeSelection::SceneChange => "scene",
+    };
+    let cache_key = format!(
+        "keyframes_v2_{}_{}_{}_{}",
+        compute_cache_key(file_path, file_mtime, file_size),
+        mode,
+        count,
+        target_size
+    );
+    let manifest_path = cache_dir.join(format!("{}.json", cache_key));
+    let sheet_path = cache_dir.join(format!("{}_sheet.jpg", cache_key));
+
+    if let Some(mut cached) = fs::read(&manifest_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<VideoKeyframesResult>(&data).ok())
+        .filter(|m| m.frame_paths.iter().all(|p| Path::new(p).exists()))
+    {
+        if contact_sheet {
+            if !sheet_path.exists() {
+                let decoded: Vec<DynamicImage> = cached
+                    .frame_paths
+                    .iter()
+                    .filter_map(|p| image::open(p).ok())
+                    .collect();
+                write_contact_sheet(&decoded, &sheet_path)?;
+            }
+            cached.contact_sheet_path = Some(sheet_path.to_string_lossy().into_owned());
+        }
+        cached.from_cache = true;
+        return Ok(cached);
+    }
+
+    let ffmpeg = get_ffmpeg_path().ok_or_else(|| "ffmpeg not found".to_string())?;
+    let _slot = VIDEO_JOB_SLOTS.acquire();
+
+    let info = probe_video_for_frames(file_path);
+    let (new_w, new_h) = scaled_video_size(info.as_ref(), target_size);
+    let duration = info.as_ref().and_then(|i| i.duration_secs);
+
+    let mut frames = match selection {
+        KeyframeSelection::SceneChange => {
+            extract_scene_change_frames(&ffmpeg, file_path, count, new_w, new_h)
+        }
+        KeyframeSelection::Even => Vec::new(),
+    };
+
+    if (frames.len() as u32) < count {
+        let missing = count - frames.len() as u32;
+        let seek_times = match duration {
+            Some(d) => even_seek_times(d, missing),
+            None if frames.is_empty() => vec![0.0],
+            None => Vec::new(),
+        };
+        frames.extend(seek_times.into_iter().filter_map(|t| {
+            extract_video_frame(&ffmpeg, file_path, t, new_w, new_h, backend).map(|data| (t, data))
+        }));
+    }
+
+    // Scene cuts and the evenly spaced fill are interleaved in playback order.
+    frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let frames: Vec<Vec<u8>> = frames.into_iter().map(|(_, data)| data).collect();
+
+    if frames.is_empty() {
+        return Err(format!("Failed to extract frames from {}", file_path));
+    }
+
+    let mut frame_paths = Vec::with_capacity(frames.len());
+    for (i, data) in frames.iter().enumerate() {
+        let frame_key = format!("{}_{:02}", cache_key, i);
+        let saved = save_thumbnail_to_cache(&cache_dir, &frame_key, "jpg", data)
+            .ok_or_else(|| format!("Failed to write keyframe to {:?}", cache_dir))?;
+        frame_paths.push(saved.to_string_lossy().into_owned());
+    }
+
+    let mut result = VideoKeyframesResult {
+        frame_paths,
+        contact_sheet_path: None,
+        from_cache: false,
+    };
+
+    // The manifest never records the sheet, so a later call that asks for one
+    // can add it without re-extracting frames.
+    if let Ok(manifest) = serde_json::to_vec(&result) {
+        let _ = write_cache_file(&manifest_path, &manifest);
+    }
+
+    if contact_sheet {
+        let decoded: Vec<DynamicImage> = frames
+            .iter()
+            .filter_map(|data| image::load_from_memory(data).ok())
+            .collect();
+        write_contact_sheet(&decoded, &sheet_path)?;
+        result.contact_sheet_path = Some(sheet_path.to_string_lossy().into_owned());
+    }
+
+    Ok(result)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScrubStripResult {
+    /// Absolute path to a horizontal JPEG sprite of `frame_count` equally
+    /// sized frames, for hover scrubbing via `background-position`.
+    pub cache_path: Option<String>,
+    pub frame_count: u32,
+    pub frame_width: Option<u32>,
+    pub frame_height: Option<u32>,
+    pub from_cache: bool,
+}
+
+/// Most frames a scrub strip holds
+const MAX_SCRUB_FRAMES: u32 = 30;
+/// Longest edge of one scrub frame; hover previews never need more, and it
+/// keeps a full strip well inside JPEG's 65535px width limit.
+const MAX_SCRUB_FRAME_SIZE: u32 = 640;
+
+/// Builds (or returns the cached) hover-scrub sprite strip for a video.
+///
+/// Frames are grabbed with fast seeks rather than a full decode, so long clips
+/// cost the same as short ones.  Frames that fail to decode are left black to
+/// keep every tile the same width.
+pub fn generate_video_scrub_strip(
+    file_path: &str,
+    frame_count: u32,
+    target_size: u32,
+    backend: VideoBackend,
+) -> ScrubStripResult {
+    let frame_count = frame_count.clamp(2, MAX_SCRUB_FRAMES);
+    let target_size = target_size.clamp(1, MAX_SCRUB_FRAME_SIZE);
+    let empty = ScrubStripResult {
+        cache_path: None,
+        frame_count,
+        frame_width: None,
+        frame_height: None,
+        from_cache: false,
+    };
+
+    let path = PathBuf::from(file_path);
+    if !path.exists() || !path.is_file() || !is_video_file(&path) {
+        return empty;
+    }
+
+    let (file_mtime, file_size) = match get_file_metadata(file_path) {
+        Some(m) => m,
+        None => return empty,
+    };
+    let cache_key = format!(
+        "video_scrub_{}_{}_{}",
+        compute_cache_key(file_path, file_mtime, file_size),
+        frame_count,
+        target_size
+    );
+    let cache_dir = match get_app_thumbnail_cache_dir() {
+        Some(d) => d,
+        None => return empty,
+    };
+
+    if let Some(cached_path) = get_cached_thumbnail_path(&cache_dir, &cache_key, "jpg") {
+        if let Ok((w, h)) = image::image_dimensions(&cached_path) {
+            return ScrubStripResult {
+                cache_path: Some(cached_path.to_string_lossy().into_owned()),
+                frame_count,
+                frame_width: Some(w / frame_count),
+                frame_height: Some(h),
+                from_cache: true,
+            };
+        }
+    }
+
+    let ffmpeg = match get_ffmpeg_path() {
+        Some(f) => f,
+        None => return empty,
+    };
+    let _slot = VIDEO_JOB_SLOTS.acquire();
+
+    let info = probe_video_for_frames(file_path);
+    let (frame_w, frame_h) = scaled_video_size(info.as_ref(), target_size);
+    let seek_times = match info.as_ref().and_then(|i| i.duration_secs) {
+        Some(d) => even_seek_times(d, frame_count),
+        None => return empty,
+    };
+
+    let strip_w = match frame_w.checked_mul(frame_count) {
+        Some(w) => w,
+        None => return empty,
+    };
+    let mut strip = image::RgbImage::new(strip_w, frame_h);
+    let mut extracted = 0;
+    for (i, seek) in seek_times.into_iter().enumerate() {
+        let frame = extract_video_frame(&ffmpeg, file_path, seek, frame_w, frame_h, backend)
+            .and_then(|data| image::load_from_memory(&data).ok());
+        if let Some(frame) = frame {
+            let frame = frame.resize_exact(frame_w, frame_h, FilterType::Triangle);
+            image::imageops::replace(&mut strip, &frame.to_rgb8(), (i as u32 * frame_w) as i64, 0);
+            extracted += 1;
+        }
+    }
+    if extracted == 0 {
+        return empty;
+    }
+
+    let jpeg_data = encode_jpeg_fast(&DynamicImage::ImageRgb8(strip), 75);
+    ScrubStripResult {
+        cache_path: save_thumbnail_to_cache(&cache_dir, &cache_key, "jpg", &jpeg_data)
+            .map(|p| p.to_string_lossy().into_owned()),
+        frame_count,
+        frame_width: Some(frame_w),
+        frame_height: Some(frame_h),
+        from_cache: false,
+    }
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::services::process::{self, RunOptions};
+
+lazy_static! {
+    static ref LOCATOR: RwLock<ToolLocator> = RwLock::new(ToolLocator::default());
+}
+
+/// Target triple this binary was built for, used for Tauri sidecar names
+/// such as `ffmpeg-x86_64-unknown-linux-gnu`.
+const TARGET_TRIPLE: &str = env!("TARGET_TRIPLE");
+
+const VERSION_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// External command-line tools the backend shells out to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExternalTool {
+    Exiftool,
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl ExternalTool {
+    pub const ALL: [ExternalTool; 3] = [
+        ExternalTool::Exiftool,
+        ExternalTool::Ffmpeg,
+        ExternalTool::Ffprobe,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ExternalTool::Exiftool => "exiftool",
+            ExternalTool::Ffmpeg => "ffmpeg",
+            ExternalTool::Ffprobe => "ffprobe",
+        }
+    }
+
+    fn file_name(&self) -> String {
+        if cfg!(target_os = "windows") {
+            format!("{}.exe", self.name())
+        } else {
+            self.name().to_string()
+        }
+    }
+
+    fn sidecar_file_name(&self) -> String {
+        if cfg!(target_os = "windows") {
+            format!("{}-{}.exe", self.name(), TARGET_TRIPLE)
+        } else {
+            format!("{}-{}", self.name(), TARGET_TRIPLE)
+        }
+    }
+
+    fn version_args(&self) -> &'static [&'static str] {
+        match self {
+            ExternalTool::Exiftool => &["-ver"],
+            ExternalTool::Ffmpeg | ExternalTool::Ffprobe => &["-hide_banner", "-version"],
+        }
+    }
+
+    /// Oldest major version known to support the flags we rely on
+    /// (`-G1`/`-json` for exiftool, `image2pipe`/`-print_format json` for ffmpeg).
+    fn min_major_version(&self) -> u32 {
+        match self {
+            ExternalTool::Exiftool => 10,
+            ExternalTool::Ffmpeg | ExternalTool::Ffprobe => 4,
+        }
+    }
+
+    /// Extracts the version string from the tool's version output.
+    fn parse_version(&self, stdout: &str) -> Option<String> {
+        let first_line = stdout.lines().next()?.trim();
+        match self {
+            ExternalTool::Exiftool => Some(first_line.to_string()).filter(|v| !v.is_empty()),
+            // "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) ..."
+            ExternalTool::Ffmpeg | ExternalTool::Ffprobe => first_line
+                .split_whitespace()
+                .skip_while(|w| *w != "version")
+                .nth(1)
+                .map(|v| v.to_string()),
+        }
+    }
+}
+
+/// Where a tool binary was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    UserConfigured,
+    Resources,
+    Sidecar,
+    ExecutableDir,
+    SystemPath,
+}
+
+/// Result of locating and validating one external tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStatus {
+    pub tool: ExternalTool,
+    pub path: Option<String>,
+    pub source: Option<ToolSource>,
+    pub version: Option<String>,
+    pub available: bool,
+    /// Why the tool is unavailable, or a warning about the one that was found
+    pub message: Option<String>,
+    /// Every location that was checked, in order
+    pub searched: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ToolConfig {
+    #[serde(default)]
+    user_paths: HashMap<ExternalTool, PathBuf>,
+}
+
+#[derive(Default)]
+struct ToolLocator {
+    resource_dir: Option<PathBuf>,
+    config: ToolConfig,
+    resolved: HashMap<ExternalTool, PathBuf>,
+}
+
+fn get_tool_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("descify").join("tools.json"))
+}
+
+fn load_tool_config() -> ToolConfig {
+    get_tool_config_path()
+        .and_then(|p| fs::read(p).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_tool_config(config: &ToolConfig) -> Result<(), String> {
+    let path = get_tool_config_path()
+        .ok_or_else(|| "Could not determine the config directory".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let data = serde_json::to_vec_pretty(config)
+        .map_err(|e| format!("Failed to serialize tool config: {}", e))?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+/// Initialise the locator with Tauri's resource directory and the persisted
+/// user-configured paths.  Call once from the app's setup hook.
+pub fn init(resource_dir: Option<PathBuf>) {
+    if let Ok(mut locator) = LOCATOR.write() {
+        locator.resource_dir = resource_dir;
+        locator.config = load_tool_config();
+        locator.resolved.clear();
+    }
+}
+
+/// Candidate locations in priority order.  The user-configured path always
+/// wins so a broken bundled binary can be worked around from settings.
+fn candidate_paths(
+    tool: ExternalTool,
+    resource_dir: Option<&Path>,
+    user_path: Option<&Path>,
+) -> Vec<(ToolSource, PathBuf)> {
+    let mut candidates = Vec::new();
+
+    if let Some(path) = user_path {
+        candidates.push((ToolSource::UserConfigured, path.to_path_buf()));
+    }
+
+    if let Some(dir) = resource_dir {
+        candidates.push((ToolSource::Resources, dir.join(tool.file_name())));
+        candidates.push((
+            ToolSource::Resources,
+            dir.join("resources").join(tool.file_name()),
+        ));
+    }
+
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+    {
+        // Tauri strips the target triple from sidecars when bundling, but
+        // `tauri dev` runs them with the suffix still attached.
+        candidates.push((ToolSource::Sidecar, exe_dir.join(tool.sidecar_file_name())));
+        candidates.push((ToolSource::ExecutableDir, exe_dir.join(tool.file_name())));
+        candidates.push((
+            ToolSource::ExecutableDir,
+            exe_dir.join("resources").join(tool.file_name()),
+        ));
+    }
+
+    candidates
+}
+
+/// Runs the tool's version command and checks it against the minimum.
+fn validate_tool(tool: ExternalTool, path: &Path) -> Result<String, String> {
+    let mut cmd = Command::new(path);
+    cmd.args(tool.version_args());
+    let output = process::run(
+        cmd,
+        &RunOptions::with_timeout(VERSION_CHECK_TIMEOUT).max_stdout(64 * 1024),
+    )
+    .map_err(|e| format!("{:?}: {}", path, e))?;
+
+    if !output.success() {
+        return Err(format!(
+            "{:?} exited with code {} when asked for its version",
+            path,
+            output.status.code().unwrap_or(-1)
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = tool
+        .parse_version(&stdout)
+        .ok_or_else(|| format!("Could not read the version of {:?}", path))?;
+
+    // Git builds of ffmpeg report e.g. "N-113406-g..." and have no major.
+    let major = version
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|m| m.parse::<u32>().ok());
+    if let Some(major) = major {
+        if major < tool.min_major_version() {
+            return Err(format!(
+                "{} {} is too old (need {} or newer)",
+                tool.name(),
+                version,
+                tool.min_major_version()
+            ));
+        }
+    }
+
+    Ok(version)
+}
+
+/// Locate and validate `tool`, recording every location that was tried.
+pub fn tool_status(tool: ExternalTool) -> ToolStatus {
+    let (resource_dir, user_path) = match LOCATOR.read() {
+        Ok(locator) => (
+            locator.resource_dir.clone(),
+            locator.config.user_paths.get(&tool).cloned(),
+        ),
+        Err(_) => (None, None),
+    };
+
+    let mut searched = Vec::new();
+    let mut rejected = Vec::new();
+    let candidates = candidate_paths(tool, resource_dir.as_deref(), user_path.as_deref());
+    let from_path = which::which(tool.name()).ok();
+    if from_path.is_none() {
+        searched.push(format!("{} on PATH", tool.name()));
+    }
+    let from_path = from_path.map(|p| (ToolSource::SystemPath, p));
+
+    for (source, path) in candidates.into_iter().chain(from_path) {
+        searched.push(path.to_string_lossy().into_owned());
+        if !path.is_file() {
+            if source == ToolSource::UserConfigured {
+                rejected.push(format!("configured path {:?} does not exist", path));
+            }
+            continue;
+        }
+        match validate_tool(tool, &path) {
+            Ok(version) => {
+                return ToolStatus {
+                    tool,
+                    path: Some(path.to_string_lossy().into_owned()),
+                    source: Some(source),
+                    version: Some(version),
+                    available: true,
+                    message: if rejected.is_empty() {
+                        None
+                    } else {
+                        Some(format!("Skipped: {}", rejected.join("; ")))
+                    },
+                    searched,
+                };
+            }
+            Err(e) => rejected.push(e),
+        }
+    }
+
+    let message = if rejected.is_empty() {
+        format!(
+            "{} was not found in the app resources, next to the executable, or on PATH",
+            tool.name()
+        )
+    } else {
+        rejected.join("; ")
+    };
+
+    ToolStatus {
+        tool,
+        path: None,
+        source: None,
+        version: None,
+        available: false,
+        message: Some(message),
+        searched,
+    }
+}
+
+/// Path of a working `tool` binary, resolved once and then cached.
+pub fn tool_path(tool: ExternalTool) -> Option<PathBuf> {
+    if let Some(path) = LOCATOR
+        .read()
+        .ok()
+        .and_then(|l| l.resolved.get(&tool).cloned())
+    {
+        return Some(path);
+    }
+
+    let path = tool_status(tool).path.map(PathBuf::from)?;
+    if let Ok(mut locator) = LOCATOR.write() {
+        locator.resolved.insert(tool, path.clone());
+    }
+    Some(path)
+}
+
+/// Set (or with `None`, clear) the user-configured path for `tool`.  The
+/// setting is persisted and takes effect immediately.
+pub fn set_user_tool_path(tool: ExternalTool, path: Option<String>) -> Result<ToolStatus, String> {
+    let path = path
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from);
+
+    if let Some(ref p) = path {
+        if !p.is_file() {
+            return Err(format!("File does not exist: {}", p.display()));
+        }
+        validate_tool(tool, p)?;
+    }
+
+    {
+        let mut locator = LOCATOR
+            .write()
+            .map_err(|_| "Tool locator is unavailable".to_string())?;
+        match path {
+            Some(p) => locator.config.user_paths.insert(tool, p),
+            None => locator.config.user_paths.remove(&tool),
+        };
+        locator.resolved.remove(&tool);
+        save_tool_config(&locator.config)?;
+    }
+
+    Ok(tool_status(tool))
+}
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::services::process::{self, RunOptions};
+use crate::services::tools::{tool_path, ExternalTool};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Typed summary of a video file, read from `ffprobe -print_format json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VideoInfo {
+    pub file_path: String,
+    /// Coded frame size of the first video stream, before rotation.
+    pub width: u32,
+    pub height: u32,
+    /// Clockwise display rotation in degrees: 0, 90, 180 or 270.
+    pub rotation: u32,
+    pub duration_secs: Option<f64>,
+    pub fps: Option<f64>,
+    pub codec: Option<String>,
+    /// Overall container bitrate in bits per second.
+    pub bitrate: Option<u64>,
+    pub has_audio: bool,
+    pub creation_time: Option<String>,
+}
+
+impl VideoInfo {
+    /// Frame size as it is displayed, i.e. with width/height swapped for
+    /// portrait phone clips that are stored landscape plus a rotation flag.
+    pub fn display_dimensions(&self) -> (u32, u32) {
+        if self.rotation == 90 || self.rotation == 270 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    tags: ProbeTags,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: ProbeTags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProbeTags {
+    rotate: Option<String>,
+    creation_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeSideData {
+    rotation: Option<f64>,
+}
+
+pub fn get_ffprobe_path() -> Option<PathBuf> {
+    tool_path(ExternalTool::Ffprobe)
+}
+
+/// Parses an ffprobe rational such as `30000/1001`.  `0/0` means unknown.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let num: f64 = num.trim().parse().ok()?;
+    let den: f64 = den.trim().parse().ok()?;
+    if num <= 0.0 || den <= 0.0 {
+        return None;
+    }
+    Some(num / den)
+}
+
+/// Normalises a rotation in degrees to one of 0/90/180/270.
+fn normalize_rotation(degrees: f64) -> u32 {
+    let quarter_turns = (degrees / 90.0).round() as i64;
+    (quarter_turns.rem_euclid(4) * 90) as u32
+}
+
+fn parse_probe_output(file_path: &str, json: &str) -> Result<VideoInfo, String> {
+    let probe: ProbeOutput =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    let video = probe
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("video"))
+        .ok_or_else(|| format!("No video stream found in {}", file_path))?;
+
+    let (width, height) = match (video.width, video.height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => (w, h),
+        _ => return Err(format!("Video stream has no dimensions: {}", file_path)),
+    };
+
+    // Newer ffmpeg reports rotation as a display-matrix side data entry
+    // (counter-clockwise), older builds as a `rotate` tag (clockwise).
+    let rotation = video
+        .side_data_list
+        .iter()
+        .find_map(|d| d.rotation)
+        .map(|r| normalize_rotation(-r))
+        .or_else(|| {
+            video
+                .tags
+                .rotate
+                .as_deref()
+                .and_then(|r| r.trim().parse::<f64>().ok())
+                .map(normalize_rotation)
+        })
+        .unwrap_or(0);
+
+    let format = probe.format.as_ref();
+    let duration_secs = format
+        .and_then(|f| f.duration.as_deref())
+        .or(video.duration.as_deref())
+        .and_then(|d| d.trim().parse::<f64>().ok())
+        .filter(|d| *d > 0.0);
+
+    let fps = video
+        .avg_frame_rate
+        .as_deref()
+        .and_then(parse_rate)
+        .or_else(|| video.r_frame_rate.as_deref().and_then(parse_rate));
+
+    let bitrate = format
+        .and_then(|f| f.bit_rate.as_deref())
+        .and_then(|b| b.trim().parse::<u64>().ok());
+
+    let creation_time = format
+        .and_then(|f| f.tags.creation_time.clone())
+        .or_else(|| video.tags.creation_time.clone());
+
+    let has_audio = probe
+        .streams
+        .iter()
+        .any(|s| s.codec_type.as_deref() == Some("audio"));
+
+    Ok(VideoInfo {
+        file_path: file_path.to_string(),
+        width,
+        height,
+        rotation,
+        duration_secs,
+        fps,
+        codec: video.codec_name.clone(),
+        bitrate,
+        has_audio,
+        creation_time,
+    })
+}
+
+/// Probe a video file with ffprobe and return its stream information
+pub fn probe_video(file_path: &str) -> Result<VideoInfo, String> {
+    let path = Path::new(file_path);
+    if !path.exists() {
+        return Err(format!("File does not exist: {}", file_path));
+    }
+    if !path.is_file() {
+        return Err(format!("Path is not a file: {}", file_path));
+    }
+
+    let ffprobe = get_ffprobe_path().ok_or_else(|| "ffprobe not found".to_string())?;
+
+    let mut cmd = Command::new(&ffprobe);
+    cmd.args([
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+    ])
+    .arg(file_path);
+
+    let output = process::run(cmd, &RunOptions::with_timeout(PROBE_TIMEOUT))
+        .and_then(|o| o.check("ffprobe"))
+        .map_err(|e| e.to_string())?;
+
+    parse_probe_output(file_path, &String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rates() {
+        let ntsc = parse_rate("30000/1001").unwrap();
+        assert!((ntsc - 29.97).abs() < 0.01);
+        assert_eq!(parse_rate("25/1"), Some(25.0));
+        assert_eq!(parse_rate("0/0"), None);
+        assert_eq!(parse_rate("30/0"), None);
+        assert_eq!(parse_rate("25"), None);
+        assert_eq!(parse_rate("abc/1"), None);
+    }
+
+    #[test]
+    fn normalizes_rotation() {
+        assert_eq!(normalize_rotation(0.0), 0);
+        assert_eq!(normalize_rotation(90.0), 90);
+        assert_eq!(normalize_rotation(-90.0), 270);
+        assert_eq!(normalize_rotation(180.0), 180);
+        assert_eq!(normalize_rotation(-180.0), 180);
+        assert_eq!(normalize_rotation(450.0), 90);
+        assert_eq!(normalize_rotation(89.6), 90);
+    }
+
+    #[test]
+    fn parses_a_portrait_phone_clip() {
+        // Display matrix rotation is counter-clockwise: -90 means the frame
+        // is shown turned 90 degrees clockwise.
+        let json = r#"{
+            "streams": [
+                {
+                    "codec_type": "video",
+                    "codec_name": "hevc",
+                    "width": 1920,
+                    "height": 1080,
+                    "avg_frame_rate": "30000/1001",
+                    "r_frame_rate": "30/1",
+                    "duration": "12.500000",
+                    "side_data_list": [{ "side_data_type": "Display Matrix", "rotation": -90 }]
+                },
+                { "codec_type": "audio", "codec_name": "aac" }
+            ],
+            "format": {
+                "duration": "12.533333",
+                "bit_rate": "15873512",
+                "tags": { "creation_time": "2024-06-01T18:22:05.000000Z" }
+            }
+        }"#;
+        let info = parse_probe_output("clip.mov", json).unwrap();
+        assert_eq!((info.width, info.height), (1920, 1080));
+        assert_eq!(info.rotation, 90);
+        assert_eq!(info.display_dimensions(), (1080, 1920));
+        assert_eq!(info.duration_secs, Some(12.533333));
+        assert!((info.fps.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(info.codec.as_deref(), Some("hevc"));
+        assert_eq!(info.bitrate, Some(15_873_512));
+        assert!(info.has_audio);
+        assert_eq!(
+            info.creation_time.as_deref(),
+            Some("2024-06-01T18:22:05.000000Z")
+        );
+    }
+
+    #[test]
+    fn reads_the_legacy_rotate_tag_as_clockwise() {
+        let json = r#"{
+            "streams": [{
+                "codec_type": "video",
+                "width": 1280,
+                "height": 720,
+                "tags": { "rotate": "270" }
+            }]
+        }"#;
+        let info = parse_probe_output("old.mp4", json).unwrap();
+        assert_eq!(info.rotation, 270);
+        assert_eq!(info.display_dimensions(), (720, 1280));
+    }
+
+    #[test]
+    fn unknown_rates_and_durations_are_none() {
+        let json = r#"{
+            "streams": [{
+                "codec_type": "video",
+                "width": 640,
+                "height": 480,
+                "avg_frame_rate": "0/0",
+                "r_frame_rate": "0/0",
+                "duration": "N/A"
+            }],
+            "format": { "duration": "N/A", "bit_rate": "N/A" }
+        }"#;
+        let info = parse_probe_output("stream.webm", json).unwrap();
+        assert_eq!(info.rotation, 0);
+        assert_eq!(info.fps, None);
+        assert_eq!(info.duration_secs, None);
+        assert_eq!(info.bitrate, None);
+        assert!(!info.has_audio);
+    }
+
+    #[test]
+    fn falls_back_to_stream_duration_and_r_frame_rate() {
+        let json = r#"{
+            "streams": [{
+                "codec_type": "video",
+                "width": 640,
+                "height": 480,
+                "avg_frame_rate": "0/0",
+                "r_frame_rate": "25/1",
+                "duration": "4.0"
+            }],
+            "format": {}
+        }"#;
+        let info = parse_probe_output("clip.mp4", json).unwrap();
+        assert_eq!(info.duration_secs, Some(4.0));
+        assert_eq!(info.fps, Some(25.0));
+    }
+
+    #[test]
+    fn rejects_files_without_a_usable_video_stream() {
+        let audio_only = r#"{"streams":[{"codec_type":"audio"}],"format":{}}"#;
+        assert!(parse_probe_output("a.m4a", audio_only)
+            .unwrap_err()
+            .contains("No video stream"));
+
+        assert!(parse_probe_output("empty.mp4", r#"{}"#).is_err());
+
+        let no_size = r#"{"streams":[{"codec_type":"video","width":0,"height":0}]}"#;
+        assert!(parse_probe_output("x.mp4", no_size)
+            .unwrap_err()
+            .contains("no dimensions"));
+
+        assert!(parse_probe_output("x.mp4", "not json").is_err());
+    }
+}
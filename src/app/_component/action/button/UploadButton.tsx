@@ -26,6 +26,7 @@ const fileFromPath = async (path: string, loadContent: boolean = true): Promise<
   const mimeTypes: Record<string, string> = {
     'jpg': 'image/jpeg', 'jpeg': 'image/jpeg', 'png': 'image/png',
     'gif': 'image/gif', 'webp': 'image/webp', 'svg': 'image/svg+xml',
+    'heic': 'image/heic', 'heif': 'image/heif', 'avif': 'image/avif',
     'mp4': 'video/mp4', 'mov': 'video/quicktime', 'webm': 'video/webm',
   };
   return new File([data], name, { type: mimeTypes[ext] || '' });
@@ -49,7 +50,7 @@ const UploadButtonComponent = ({ }: UploadButtonProps) => {
       filters: [
         {
           name: "Media",
-          extensions: ["jpg", "jpeg", "png", "gif", "webp", "svg", "mp4", "mov", "webm"]
+          extensions: ["jpg", "jpeg", "png", "gif", "webp", "svg", "heic", "heif", "avif", "mp4", "mov", "webm"]
         }
       ]
     });
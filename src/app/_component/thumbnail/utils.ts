@@ -13,6 +13,9 @@ export const getMimeType = (filename: string): string => {
     'bmp': 'image/bmp',
     'tiff': 'image/tiff',
     'svg': 'image/svg+xml',
+    'heic': 'image/heic',
+    'heif': 'image/heif',
+    'avif': 'image/avif',
     // Videos
     'mp4': 'video/mp4',
     'mov': 'video/quicktime',
@@ -7,6 +7,7 @@ import * as templateSlice from '../../store/slices/templateSlice';
 import * as uiSlice from '../../store/slices/uiSlice';
 import * as batchSlice from '../../store/slices/batchSlice';
 import { BATCH_CONFIG } from '@/app/lib/thumbnailGenerator';
+import { storeApiKey, deleteApiKey, type AiProviderKind } from '@/app/lib/tauri-commands';
 
 // Re-export types from slices to maintain compatibility
 export type Provider = configSlice.Provider;
@@ -119,6 +120,23 @@ export type SettingsContextType = {
 
 const SettingsContext = createContext<SettingsContextType | undefined>(undefined);
 
+// Settings providers the backend can call, by their backend name
+const BACKEND_PROVIDERS: Partial<Record<Provider, AiProviderKind>> = {
+  openai: 'openai',
+  gemini: 'google',
+  openrouter: 'openrouter',
+};
+
+// Replace the backend's stored keys for a provider with the settings key
+async function syncBackendApiKey(provider: Provider, key: string): Promise<void> {
+  const backendProvider = BACKEND_PROVIDERS[provider];
+  if (!backendProvider) return;
+  await deleteApiKey(backendProvider);
+  if (key.trim()) {
+    await storeApiKey(backendProvider, key.trim());
+  }
+}
+
 export const SettingsProvider = ({ children }: { children: ReactNode }) => {
   const dispatch = useAppDispatch();
 
@@ -141,6 +159,9 @@ export const SettingsProvider = ({ children }: { children: ReactNode }) => {
 
   const setApiKey = useCallback((provider: Provider, key: string) => {
     dispatch(configSlice.setApiKey({ provider, key }));
+    syncBackendApiKey(provider, key).catch((error) => {
+      console.warn(`⚠️ Failed to store the ${provider} API key on the backend:`, error);
+    });
   }, [dispatch]);
 
   const setRequestDelay = useCallback((delay: number) => {
@@ -540,6 +561,20 @@ export const SettingsProvider = ({ children }: { children: ReactNode }) => {
     })();
   }, [fileState.files]); // Only depend on files
 
+  // One-time hand-over of keys saved before the backend stored its own, so
+  // batch jobs can use them without the webview sending them along
+  useEffect(() => {
+    if (config.api.apiKeysMigrated) return;
+    const keys = Object.entries(config.api.apiKeys) as [Provider, string][];
+    Promise.all(
+      keys.filter(([, key]) => key?.trim()).map(([provider, key]) => syncBackendApiKey(provider, key))
+    )
+      .then(() => dispatch(configSlice.setApiKeysMigrated(true)))
+      .catch((error) => {
+        console.warn('⚠️ Failed to move API keys to the backend, will retry next launch:', error);
+      });
+  }, [config.api.apiKeysMigrated, dispatch]); // Keys are read once, when not yet migrated
+
   // Ensure export settings are properly initialized
   useEffect(() => {
     if (!config.exportSettings || config.exportSettings.shutterStock === false) {
@@ -1,10 +1,22 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 
 export interface EmbedMetadataRequest {
   file_path: string;
   title?: string;
   description?: string;
   keywords?: string;
+  camera_make?: string;
+  camera_model?: string;
+  lens?: string;
+  /** YYYY-MM-DD HH:MM:SS as recorded by the camera */
+  capture_date?: string;
+  gps_latitude?: number;
+  gps_longitude?: number;
+  sublocation?: string;
+  city?: string;
+  state?: string;
+  country?: string;
 }
 
 export interface EmbedMetadataResult {
@@ -35,6 +47,525 @@ export async function readExifMetadata(filePath: string): Promise<ExifData> {
   return await invoke('read_exif_metadata_command', { filePath });
 }
 
+// Interface for video stream information (from ffprobe)
+export interface VideoInfo {
+  file_path: string;
+  width: number;
+  height: number;
+  rotation: number;
+  duration_secs?: number;
+  fps?: number;
+  codec?: string;
+  bitrate?: number;
+  has_audio: boolean;
+  creation_time?: string;
+}
+
+/**
+ * Read size, rotation, duration and codec information from a video file
+ */
+export async function readVideoInfo(filePath: string): Promise<VideoInfo> {
+  return await invoke('read_video_info', { filePath });
+}
+
+export type KeyframeSelection = 'even' | 'scene_change';
+
+export interface VideoKeyframesResult {
+  frame_paths: string[];
+  contact_sheet_path?: string;
+  from_cache: boolean;
+}
+
+/**
+ * Extract evenly spaced or scene-change frames from a video (cached on disk),
+ * optionally with a tiled contact sheet for sending the clip to an AI model
+ */
+export async function generateVideoKeyframes(
+  filePath: string,
+  options: { count?: number; size?: number; selection?: KeyframeSelection; contactSheet?: boolean } = {}
+): Promise<VideoKeyframesResult> {
+  return await invoke('generate_video_keyframes', { filePath, ...options });
+}
+
+export interface ScrubStripResult {
+  cache_path: string | null;
+  frame_count: number;
+  frame_width: number | null;
+  frame_height: number | null;
+  from_cache: boolean;
+}
+
+/**
+ * Build (or load from cache) a horizontal sprite strip of evenly spaced
+ * frames for hover-scrubbing a video in the grid
+ */
+export async function generateVideoScrubStrip(
+  filePath: string,
+  frameCount?: number,
+  size?: number
+): Promise<ScrubStripResult> {
+  return await invoke('generate_video_scrub_strip_command', { filePath, frameCount, size });
+}
+
+export type VideoBackend = 'cuda' | 'vaapi' | 'video_toolbox' | 'cpu';
+
+export interface HardwareStatus {
+  nvidia_gpu: boolean;
+  amd_gpu: boolean;
+  ffmpeg_hwaccels: string[];
+  detected_backend: VideoBackend;
+  /** Persisted across restarts */
+  force_cpu: boolean;
+  active_backend: VideoBackend;
+  /** Startup probe still running; video uses the CPU until it finishes */
+  detecting: boolean;
+}
+
+/**
+ * Get the GPU/ffmpeg capabilities detected at startup
+ */
+export async function getHardwareCapabilities(): Promise<HardwareStatus> {
+  return await invoke('get_hardware_capabilities');
+}
+
+/**
+ * Force video frame extraction onto the CPU (or restore hardware decoding)
+ */
+export async function setForceCpuVideo(forceCpu: boolean): Promise<HardwareStatus> {
+  return await invoke('set_force_cpu_video', { forceCpu });
+}
+
+export type ExternalTool = 'exiftool' | 'ffmpeg' | 'ffprobe';
+
+export type ToolSource =
+  | 'user_configured'
+  | 'resources'
+  | 'sidecar'
+  | 'executable_dir'
+  | 'system_path';
+
+export interface ToolStatus {
+  tool: ExternalTool;
+  path: string | null;
+  source: ToolSource | null;
+  version: string | null;
+  available: boolean;
+  message: string | null;
+  searched: string[];
+}
+
+/**
+ * Report where exiftool, ffmpeg and ffprobe were found and why any are missing
+ */
+export async function getToolStatus(): Promise<ToolStatus[]> {
+  return await invoke('get_tool_status');
+}
+
+/**
+ * Use a specific binary for a tool; pass null to return to automatic discovery
+ */
+export async function setToolPath(tool: ExternalTool, path: string | null): Promise<ToolStatus> {
+  return await invoke('set_tool_path', { tool, path });
+}
+
+export type AiProviderKind = 'google' | 'openai' | 'openrouter' | 'local' | 'ollama';
+
+export interface AiGenerateOptions {
+  provider: AiProviderKind;
+  model?: string;
+  /**
+   * Endpoint override; required for `local` (e.g. http://localhost:1234/v1).
+   * For `ollama` the server root, default http://localhost:11434
+   */
+  base_url?: string;
+  /** Fully rendered prompt text; ignored when prompt_template is set */
+  prompt?: string;
+  /** Template rendered per file on the backend with its EXIF data */
+  prompt_template?: string;
+  image_size?: number;
+  max_tokens?: number;
+  temperature?: number;
+  /** Count and length limits checked after parsing; violations become warnings */
+  keyword_limit?: number;
+  title_limit?: number;
+  description_limit?: number;
+  /** Use the provider's JSON-schema output mode (default: on for google and openai) */
+  structured_output?: boolean;
+  /** Send the request even when an identical one was already answered */
+  force_refresh?: boolean;
+  /** Ollama only: how long the model stays loaded, e.g. '10m', '1h' or '-1' */
+  keep_alive?: string;
+}
+
+export interface AiMetadataResult {
+  title: string;
+  description: string;
+  keywords: string[];
+  provider: AiProviderKind;
+  model: string;
+  /** Limit violations, e.g. "Title is 214 characters, 14 over the 200 character limit" */
+  warnings: string[];
+  usage: TokenUsage | null;
+  /** Estimated cost of this request in USD; null when the model has no price */
+  cost_usd: number | null;
+  /** Answered from the response cache; no request was sent */
+  cached: boolean;
+}
+
+/**
+ * Generate title, description and keywords for a file from the Rust backend.
+ * The image is prepared with the native preview pipeline (videos included).
+ */
+export async function generateMetadataRust(
+  filePath: string,
+  options: AiGenerateOptions
+): Promise<AiMetadataResult> {
+  return await invoke('generate_metadata', { filePath, options });
+}
+
+export interface OllamaModel {
+  name: string;
+  /** Size on disk in bytes */
+  size: number;
+  modified_at: string | null;
+  details: {
+    family: string | null;
+    /** Vision models include a projector family such as 'clip' */
+    families: string[] | null;
+    parameter_size: string | null;
+    quantization_level: string | null;
+  } | null;
+}
+
+/**
+ * Models installed on an Ollama server (default http://localhost:11434)
+ */
+export async function listOllamaModels(baseUrl?: string): Promise<OllamaModel[]> {
+  return await invoke('list_ollama_models', { baseUrl });
+}
+
+export interface RateLimitStatus {
+  provider: AiProviderKind;
+  /** Short hash of the API key, never the key itself */
+  key_id: string;
+  requests_per_minute: number;
+  tokens_available: number;
+  burst: number;
+  concurrency_limit: number;
+  in_flight: number;
+  /** Remaining cooldown after a 429/503 response */
+  blocked_for_ms: number;
+  total_requests: number;
+  throttled_responses: number;
+  last_error: string | null;
+}
+
+/**
+ * Current adaptive rate limits per provider and key
+ */
+export async function getRateLimitStatus(): Promise<RateLimitStatus[]> {
+  return await invoke('get_rate_limit_status');
+}
+
+export type SecretBackend = 'keyring' | 'encrypted_file';
+
+export interface StoredApiKey {
+  provider: AiProviderKind;
+  /** Same id reported by getKeyPoolStatus and getRateLimitStatus */
+  key_id: string;
+  /** Last few characters of the key */
+  hint: string;
+  backend: SecretBackend;
+}
+
+/**
+ * Store an API key on the backend (system keyring, or an encrypted file when
+ * no keyring is available). The key cannot be read back from JS afterwards.
+ */
+export async function storeApiKey(provider: AiProviderKind, apiKey: string): Promise<StoredApiKey> {
+  return await invoke('store_api_key', { provider, apiKey });
+}
+
+/**
+ * List stored API keys by id and hint
+ */
+export async function listApiKeys(): Promise<StoredApiKey[]> {
+  return await invoke('list_api_keys');
+}
+
+/**
+ * Delete one stored key, or every key for the provider when keyId is omitted
+ */
+export async function deleteApiKey(provider: AiProviderKind, keyId?: string): Promise<void> {
+  return await invoke('delete_api_key', { provider, keyId });
+}
+
+export interface TemplateError {
+  /** 1-based line in the template */
+  line: number | null;
+  message: string;
+}
+
+export interface TemplateValidation {
+  valid: boolean;
+  errors: TemplateError[];
+  warnings: string[];
+  unknown_variables: string[];
+  missing_variables: string[];
+}
+
+export interface TemplateVariable {
+  name: string;
+  description: string;
+}
+
+/**
+ * Validate a prompt template before saving it
+ */
+export async function validatePromptTemplate(template: string): Promise<TemplateValidation> {
+  return await invoke('validate_prompt_template', { template });
+}
+
+/**
+ * Variables available to prompt templates
+ */
+export async function getTemplateVariables(): Promise<TemplateVariable[]> {
+  return await invoke('get_template_variables');
+}
+
+/**
+ * Render a prompt template for a file, or with only the limits for a preview
+ */
+export async function renderPromptTemplate(
+  template: string,
+  filePath?: string,
+  limits?: { title_limit?: number; description_limit?: number; keyword_limit?: number }
+): Promise<string> {
+  return await invoke('render_prompt_template', { template, filePath, limits });
+}
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  /** Billed amount reported by the provider (OpenRouter) */
+  reported_cost: number | null;
+}
+
+export interface ModelPrice {
+  input_per_million: number;
+  output_per_million: number;
+}
+
+/** USD per million tokens, keyed by model id prefix */
+export interface PriceTable {
+  models: Record<string, ModelPrice>;
+}
+
+export interface UsageRange {
+  /** Inclusive, YYYY-MM-DD */
+  from?: string | null;
+  to?: string | null;
+  job_id?: string | null;
+}
+
+export interface UsageTotals {
+  requests: number;
+  images: number;
+  input_tokens: number;
+  output_tokens: number;
+  cost_usd: number;
+  /** Requests without a price, not included in cost_usd */
+  unpriced_requests: number;
+}
+
+export interface DailyUsage extends UsageTotals {
+  date: string;
+}
+
+export interface ModelUsage extends UsageTotals {
+  provider: AiProviderKind;
+  model: string;
+}
+
+export interface UsageReport {
+  range: UsageRange;
+  totals: UsageTotals;
+  by_day: DailyUsage[];
+  by_model: ModelUsage[];
+}
+
+/**
+ * Token usage and cost totals from the local ledger, per day and per model
+ */
+export async function getUsageReport(range?: UsageRange): Promise<UsageReport> {
+  return await invoke('get_usage_report', { range });
+}
+
+/**
+ * Prices used to estimate costs (saved table, or the built-in defaults)
+ */
+export async function getPriceTable(): Promise<PriceTable> {
+  return await invoke('get_price_table');
+}
+
+export async function setPriceTable(table: PriceTable): Promise<void> {
+  return await invoke('set_price_table', { table });
+}
+
+export type KeyHealth = 'active' | 'exhausted' | 'invalid';
+
+export interface KeyPoolStatus {
+  provider: AiProviderKind;
+  /** Short hash of the API key, never the key itself */
+  key_id: string;
+  health: KeyHealth;
+  /** Time left before an exhausted key is tried again */
+  available_in_ms: number | null;
+  requests: number;
+  successes: number;
+  failures: number;
+  last_error: string | null;
+  last_used_at: string | null;
+}
+
+/**
+ * Health and usage of every API key used this session
+ */
+export async function getKeyPoolStatus(): Promise<KeyPoolStatus[]> {
+  return await invoke('get_key_pool_status');
+}
+
+export type BatchJobStatus = 'queued' | 'running' | 'paused' | 'cancelled' | 'completed' | 'failed';
+export type BatchFileStatus = 'pending' | 'processing' | 'completed' | 'failed' | 'skipped';
+export type BatchJobStage = 'thumbnail' | 'generate' | 'validate' | 'embed' | 'export';
+
+export interface BatchJobSpec {
+  folders: {
+    folder_path: string;
+    /** Files to process; leave empty to scan the folder */
+    files?: string[];
+    /** Rendered prompt for this folder's template */
+    prompt?: string;
+    /** Template rendered per file; takes precedence over prompt */
+    template?: string;
+  }[];
+  ai: AiGenerateOptions;
+  limits?: { title_limit?: number; description_limit?: number; keyword_limit?: number };
+  generate_thumbnails?: boolean;
+  /** Omit to skip embedding */
+  embed?: { title: boolean; description: boolean; keywords: boolean };
+  /** Omit to skip CSV export */
+  export?: {
+    platform: 'adobe_stock' | 'shutterstock';
+    directory: string;
+    adobe_category?: string;
+    shutterstock_category1?: string;
+    shutterstock_category2?: string;
+  };
+  concurrency?: number;
+  request_delay_ms?: number;
+}
+
+export interface BatchJobSummary {
+  id: string;
+  status: BatchJobStatus;
+  folder_count: number;
+  total_files: number;
+  completed_files: number;
+  failed_files: number;
+  error: string | null;
+  created_at: number;
+  updated_at: number;
+}
+
+export interface BatchFileState {
+  file_path: string;
+  status: BatchFileStatus;
+  stage: BatchJobStage | null;
+  metadata: { title: string; description: string; keywords: string[] } | null;
+  warnings: string[];
+  error: string | null;
+  embedded: boolean;
+  /** Metadata came from the response cache */
+  cached: boolean;
+}
+
+export interface BatchJob {
+  id: string;
+  status: BatchJobStatus;
+  spec: BatchJobSpec;
+  folders: {
+    folder_path: string;
+    folder_name: string;
+    prompt: string | null;
+    scanned: boolean;
+    files: BatchFileState[];
+    export_path: string | null;
+    error: string | null;
+  }[];
+  error: string | null;
+  created_at: number;
+  updated_at: number;
+}
+
+export interface BatchJobProgressEvent {
+  job_id: string;
+  status: BatchJobStatus;
+  folder_index: number | null;
+  file_path: string | null;
+  stage: BatchJobStage | null;
+  file_status: BatchFileStatus | null;
+  metadata: BatchFileState['metadata'];
+  error: string | null;
+  export_path: string | null;
+  total_files: number;
+  completed_files: number;
+  failed_files: number;
+}
+
+/**
+ * Start a batch job on the backend; it keeps running across webview reloads
+ */
+export async function startBatchJob(spec: BatchJobSpec): Promise<BatchJobSummary> {
+  return await invoke('start_batch_job', { spec });
+}
+
+export async function pauseBatchJob(jobId: string): Promise<BatchJobSummary> {
+  return await invoke('pause_batch_job', { jobId });
+}
+
+/**
+ * Resume a paused or interrupted job with the API keys stored on the backend.
+ */
+export async function resumeBatchJob(jobId: string): Promise<BatchJobSummary> {
+  return await invoke('resume_batch_job', { jobId });
+}
+
+export async function cancelBatchJob(jobId: string): Promise<BatchJobSummary> {
+  return await invoke('cancel_batch_job', { jobId });
+}
+
+export async function getBatchJob(jobId: string): Promise<BatchJob> {
+  return await invoke('get_batch_job', { jobId });
+}
+
+export async function listBatchJobs(): Promise<BatchJobSummary[]> {
+  return await invoke('list_batch_jobs');
+}
+
+export async function deleteBatchJob(jobId: string): Promise<void> {
+  return await invoke('delete_batch_job', { jobId });
+}
+
+/**
+ * Subscribe to progress events from all backend batch jobs
+ */
+export async function onBatchJobProgress(
+  handler: (event: BatchJobProgressEvent) => void
+): Promise<UnlistenFn> {
+  return await listen<BatchJobProgressEvent>('batch-job-progress', (e) => handler(e.payload));
+}
+
 export interface CacheDirectory {
   name: string;
   path: string;
@@ -38,6 +38,8 @@ interface ConfigState {
     selectedProvider: Provider | '';
     selectedModel: string;
     apiKeys: Record<Provider, string>;
+    // Keys saved before the backend kept its own copy have been handed over
+    apiKeysMigrated: boolean;
     requestDelay: number;
     processingMode: ProcessingMode;
     parallelWorkers: number;
@@ -64,6 +66,7 @@ const initialState: ConfigState = {
     selectedProvider: '',
     selectedModel: '',
     apiKeys: defaultApiKeys,
+    apiKeysMigrated: false,
     requestDelay: 0,
     processingMode: 'sequential',
     parallelWorkers: 5,
@@ -114,6 +117,9 @@ const configSlice = createSlice({
     setApiKeys(state, action: PayloadAction<Record<Provider, string>>) {
       state.api.apiKeys = action.payload;
     },
+    setApiKeysMigrated(state, action: PayloadAction<boolean>) {
+      state.api.apiKeysMigrated = action.payload;
+    },
     setRequestDelay(state, action: PayloadAction<number>) {
       state.api.requestDelay = action.payload;
     },
@@ -157,6 +163,7 @@ export const {
   setSelectedModel,
   setApiKey,
   setApiKeys,
+  setApiKeysMigrated,
   setRequestDelay,
   setProcessingMode,
   setParallelWorkers,